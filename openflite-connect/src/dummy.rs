@@ -7,6 +7,7 @@ pub struct DummyClient {
 }
 
 impl DummyClient {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self {
             connected: false,
@@ -16,6 +17,14 @@ impl DummyClient {
 }
 
 impl SimClient for DummyClient {
+    fn name(&self) -> &str {
+        "Demo"
+    }
+
+    fn is_demo(&self) -> bool {
+        true
+    }
+
     fn connect(&mut self) -> Result<()> {
         self.connected = true;
         log::info!("DummyClient connected");
@@ -67,3 +76,15 @@ impl SimClient for DummyClient {
         vars
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SimCapabilities;
+
+    #[test]
+    fn test_capabilities() {
+        let client = DummyClient::new();
+        assert_eq!(client.capabilities(), SimCapabilities::default());
+    }
+}
@@ -1,6 +1,35 @@
 use anyhow::Result;
 
+/// Features a simulator backend supports
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SimCapabilities {
+    /// Variables can be written (Dataref / SimVar writes)
+    pub write_variables: bool,
+    /// Commands / events can be executed
+    pub commands: bool,
+    /// Variables are pushed by the sim after subscribing
+    pub subscriptions: bool,
+    /// String-valued variables can be read
+    pub string_variables: bool,
+}
+
+/// A variable the sim can provide, for discovery and config validation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VariableMeta {
+    pub name: String,
+    /// Value type as the sim reports it, e.g. "float" or "int[8]"
+    pub value_type: Option<String>,
+    pub units: Option<String>,
+    pub writable: bool,
+    pub description: Option<String>,
+}
+
 pub trait SimClient {
+    /// Human-readable backend name, e.g. for status displays
+    fn name(&self) -> &str {
+        "Simulator"
+    }
+
     /// Connect to the simulator
     fn connect(&mut self) -> Result<()>;
 
@@ -21,6 +50,26 @@ pub trait SimClient {
 
     /// Get all currently cached variables
     fn get_all_variables(&self) -> std::collections::HashMap<String, f64>;
+
+    /// The most recent problem talking to the sim, if it hasn't recovered
+    fn last_error(&self) -> Option<String> {
+        None
+    }
+
+    /// Variables the sim can provide; empty when the backend can't list them
+    fn available_variables(&self) -> Vec<VariableMeta> {
+        Vec::new()
+    }
+
+    /// Whether the backend makes its data up rather than talking to a sim
+    fn is_demo(&self) -> bool {
+        false
+    }
+
+    /// Describe what this backend supports
+    fn capabilities(&self) -> SimCapabilities {
+        SimCapabilities::default()
+    }
 }
 
 pub mod dummy;
@@ -1,17 +1,113 @@
-use crate::SimClient;
+use crate::{SimCapabilities, SimClient, VariableMeta};
 use anyhow::{anyhow, Result};
+use serde_json::Value;
 use std::collections::HashMap;
+use thiserror::Error;
 
 const DEFAULT_BRIDGE_URL: &str = "http://127.0.0.1:8080";
 
+/// Keys a bridge may wrap the variable map in, e.g. `{"variables": {...}}`
+const ENVELOPE_KEYS: [&str; 3] = ["variables", "simvars", "data"];
+
+/// Why a `/simvars` poll produced no variables
+#[derive(Debug, Error, PartialEq)]
+pub enum PollError {
+    #[error("MSFS bridge did not answer in time")]
+    Timeout,
+    #[error("MSFS bridge request failed: {0}")]
+    Http(String),
+    #[error("MSFS bridge returned HTTP {0}")]
+    Status(u16),
+    #[error("Unexpected /simvars response: {0}")]
+    Shape(String),
+}
+
+/// Read a `/simvars` body into numbers, skipping values that aren't numeric
+pub fn parse_simvars(body: &str) -> std::result::Result<HashMap<String, f64>, PollError> {
+    let value: Value =
+        serde_json::from_str(body).map_err(|e| PollError::Shape(format!("invalid JSON: {}", e)))?;
+    let Value::Object(object) = value else {
+        return Err(PollError::Shape("expected a JSON object".to_string()));
+    };
+
+    let vars = ENVELOPE_KEYS
+        .iter()
+        .find_map(|key| match object.get(*key) {
+            Some(Value::Object(inner)) => Some(inner),
+            _ => None,
+        })
+        .unwrap_or(&object);
+
+    Ok(vars
+        .iter()
+        .filter_map(|(name, value)| {
+            let number = match value {
+                Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+                _ => value.as_f64(),
+            };
+            if number.is_none() {
+                log::debug!("Skipping non-numeric simvar {}: {}", name, value);
+            }
+            Some((name.clone(), number?))
+        })
+        .collect())
+}
+
+/// Read a `/catalog` body: variable names or objects describing them
+pub fn parse_catalog(body: &str) -> std::result::Result<Vec<VariableMeta>, PollError> {
+    let value: Value =
+        serde_json::from_str(body).map_err(|e| PollError::Shape(format!("invalid JSON: {}", e)))?;
+    let entries = match &value {
+        Value::Array(entries) => entries,
+        Value::Object(object) => ENVELOPE_KEYS
+            .iter()
+            .find_map(|key| match object.get(*key) {
+                Some(Value::Array(inner)) => Some(inner),
+                _ => None,
+            })
+            .ok_or_else(|| PollError::Shape("expected a variable list".to_string()))?,
+        _ => return Err(PollError::Shape("expected a variable list".to_string())),
+    };
+
+    let text = |entry: &Value, key: &str| entry.get(key).and_then(Value::as_str).map(String::from);
+    entries
+        .iter()
+        .map(|entry| match entry {
+            Value::String(name) => Ok(VariableMeta {
+                name: name.clone(),
+                ..Default::default()
+            }),
+            Value::Object(_) => Ok(VariableMeta {
+                name: text(entry, "name").ok_or_else(|| {
+                    PollError::Shape(format!("catalog entry without a name: {}", entry))
+                })?,
+                value_type: text(entry, "type"),
+                units: text(entry, "units"),
+                writable: entry
+                    .get("writable")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false),
+                description: text(entry, "description"),
+            }),
+            _ => Err(PollError::Shape(format!(
+                "unexpected catalog entry: {}",
+                entry
+            ))),
+        })
+        .collect()
+}
+
 pub struct MSFSClient {
     connected: bool,
     bridge_url: String,
     client: reqwest::blocking::Client,
     variables: HashMap<String, f64>,
+    last_error: Option<PollError>,
+    catalog: Vec<VariableMeta>,
 }
 
 impl MSFSClient {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self {
             connected: false,
@@ -21,6 +117,8 @@ impl MSFSClient {
                 .build()
                 .unwrap(),
             variables: HashMap::new(),
+            last_error: None,
+            catalog: Vec::new(),
         }
     }
 
@@ -33,11 +131,51 @@ impl MSFSClient {
                 .build()
                 .unwrap(),
             variables: HashMap::new(),
+            last_error: None,
+            catalog: Vec::new(),
         }
     }
 }
 
+impl MSFSClient {
+    fn fetch_catalog(&self) -> std::result::Result<Vec<VariableMeta>, PollError> {
+        let url = format!("{}/catalog", self.bridge_url);
+        let resp = self.client.get(&url).send().map_err(|e| {
+            if e.is_timeout() {
+                PollError::Timeout
+            } else {
+                PollError::Http(e.to_string())
+            }
+        })?;
+        if !resp.status().is_success() {
+            return Err(PollError::Status(resp.status().as_u16()));
+        }
+        let body = resp.text().map_err(|e| PollError::Http(e.to_string()))?;
+        parse_catalog(&body)
+    }
+
+    fn fetch_simvars(&self) -> std::result::Result<HashMap<String, f64>, PollError> {
+        let url = format!("{}/simvars", self.bridge_url);
+        let resp = self.client.get(&url).send().map_err(|e| {
+            if e.is_timeout() {
+                PollError::Timeout
+            } else {
+                PollError::Http(e.to_string())
+            }
+        })?;
+        if !resp.status().is_success() {
+            return Err(PollError::Status(resp.status().as_u16()));
+        }
+        let body = resp.text().map_err(|e| PollError::Http(e.to_string()))?;
+        parse_simvars(&body)
+    }
+}
+
 impl SimClient for MSFSClient {
+    fn name(&self) -> &str {
+        "MSFS"
+    }
+
     fn connect(&mut self) -> Result<()> {
         // Try to reach the MSFS bridge
         let url = format!("{}/status", self.bridge_url);
@@ -45,6 +183,11 @@ impl SimClient for MSFSClient {
             Ok(resp) if resp.status().is_success() => {
                 log::info!("Connected to MSFS bridge at {}", self.bridge_url);
                 self.connected = true;
+                // Older bridges have no catalog; discovery just stays empty
+                match self.fetch_catalog() {
+                    Ok(catalog) => self.catalog = catalog,
+                    Err(e) => log::debug!("No MSFS variable catalog: {}", e),
+                }
                 Ok(())
             }
             Ok(resp) => Err(anyhow!("Bridge returned error: {}", resp.status())),
@@ -58,6 +201,8 @@ impl SimClient for MSFSClient {
     fn disconnect(&mut self) -> Result<()> {
         self.connected = false;
         self.variables.clear();
+        self.last_error = None;
+        self.catalog.clear();
         log::info!("Disconnected from MSFS bridge");
         Ok(())
     }
@@ -114,22 +259,147 @@ impl SimClient for MSFSClient {
             return Ok(());
         }
 
-        let url = format!("{}/simvars", self.bridge_url);
-        match self.client.get(&url).send() {
-            Ok(resp) if resp.status().is_success() => {
-                if let Ok(vars) = resp.json::<HashMap<String, f64>>() {
-                    self.variables = vars;
+        match self.fetch_simvars() {
+            Ok(vars) => {
+                if self.last_error.take().is_some() {
+                    log::info!("MSFS bridge polling recovered");
                 }
+                self.variables = vars;
             }
-            Ok(_) => {}
             Err(e) => {
-                log::warn!("Failed to poll MSFS: {}", e);
+                // Polling runs every frame; only log when the problem changes
+                if self.last_error.as_ref() != Some(&e) {
+                    log::warn!("Failed to poll MSFS: {}", e);
+                }
+                self.last_error = Some(e);
             }
         }
         Ok(())
     }
 
+    fn last_error(&self) -> Option<String> {
+        self.last_error.as_ref().map(|e| e.to_string())
+    }
+
     fn get_all_variables(&self) -> HashMap<String, f64> {
         self.variables.clone()
     }
+
+    fn available_variables(&self) -> Vec<VariableMeta> {
+        self.catalog.clone()
+    }
+
+    fn capabilities(&self) -> SimCapabilities {
+        SimCapabilities {
+            write_variables: true,
+            commands: true,
+            subscriptions: false,
+            string_variables: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities() {
+        let caps = MSFSClient::new().capabilities();
+        assert!(caps.write_variables);
+        assert!(caps.commands);
+        assert!(!caps.subscriptions);
+        assert!(!caps.string_variables);
+    }
+
+    #[test]
+    fn test_parse_flat_simvars() {
+        let vars =
+            parse_simvars(r#"{"PLANE ALTITUDE": 3500.5, "GEAR HANDLE POSITION": 1}"#).unwrap();
+        assert_eq!(vars["PLANE ALTITUDE"], 3500.5);
+        assert_eq!(vars["GEAR HANDLE POSITION"], 1.0);
+    }
+
+    #[test]
+    fn test_parse_enveloped_simvars() {
+        let vars =
+            parse_simvars(r#"{"status": "ok", "variables": {"PLANE ALTITUDE": 3500.5}}"#).unwrap();
+        assert_eq!(vars.len(), 1);
+        assert_eq!(vars["PLANE ALTITUDE"], 3500.5);
+    }
+
+    #[test]
+    fn test_parse_malformed_simvars() {
+        assert!(matches!(
+            parse_simvars("<html>502 Bad Gateway</html>"),
+            Err(PollError::Shape(msg)) if msg.starts_with("invalid JSON")
+        ));
+        assert_eq!(
+            parse_simvars("[1, 2, 3]"),
+            Err(PollError::Shape("expected a JSON object".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_simvars_skips_non_numbers() {
+        let vars = parse_simvars(
+            r#"{"ATC ID": "N172SP", "AUTOPILOT MASTER": true, "GEAR DAMAGED": false, "PLANE ALTITUDE": 3500.5}"#,
+        )
+        .unwrap();
+        assert_eq!(vars.len(), 3);
+        assert_eq!(vars["AUTOPILOT MASTER"], 1.0);
+        assert_eq!(vars["GEAR DAMAGED"], 0.0);
+        assert!(!vars.contains_key("ATC ID"));
+    }
+
+    #[test]
+    fn test_parse_catalog() {
+        let catalog = parse_catalog(
+            r#"{"variables": [
+                {"name": "PLANE ALTITUDE", "type": "float64", "units": "feet", "description": "Altitude of aircraft"},
+                {"name": "GEAR HANDLE POSITION", "units": "bool", "writable": true},
+                "L:A32NX_AUTOPILOT_1_ACTIVE"
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            catalog[0],
+            VariableMeta {
+                name: "PLANE ALTITUDE".to_string(),
+                value_type: Some("float64".to_string()),
+                units: Some("feet".to_string()),
+                writable: false,
+                description: Some("Altitude of aircraft".to_string()),
+            }
+        );
+        assert!(catalog[1].writable);
+        assert_eq!(catalog[1].value_type, None);
+        assert_eq!(catalog[2].name, "L:A32NX_AUTOPILOT_1_ACTIVE");
+        assert_eq!(catalog[2].units, None);
+
+        assert_eq!(parse_catalog(r#"["PLANE ALTITUDE"]"#).unwrap().len(), 1);
+        assert_eq!(
+            parse_catalog(r#"{"PLANE ALTITUDE": 3500}"#),
+            Err(PollError::Shape("expected a variable list".to_string()))
+        );
+        assert!(parse_catalog(r#"[{"units": "feet"}]"#).is_err());
+    }
+
+    #[test]
+    fn test_unreachable_bridge_sets_last_error() {
+        // A port that was free a moment ago, so nothing is listening on it
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut client = MSFSClient::with_url(&format!("http://127.0.0.1:{}", port));
+        client.connected = true;
+        client.poll().unwrap();
+        assert!(matches!(
+            client.last_error,
+            Some(PollError::Http(_) | PollError::Timeout)
+        ));
+        assert!(client.last_error().is_some());
+    }
 }
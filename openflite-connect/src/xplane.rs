@@ -1,14 +1,48 @@
-use crate::SimClient;
+use crate::{SimCapabilities, SimClient, VariableMeta};
 use anyhow::{anyhow, Result};
 use std::collections::HashMap;
 use std::net::UdpSocket;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
+/// Read X-Plane's `Resources/plugins/DataRefs.txt`
+pub fn parse_datarefs_txt(contents: &str) -> Vec<VariableMeta> {
+    let field = |value: Option<&str>| {
+        value
+            .map(str::trim)
+            .filter(|v| !v.is_empty() && *v != "???")
+            .map(String::from)
+    };
+
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let name = fields.next()?.trim();
+            // Skips the header and blank lines
+            if !name.contains('/') {
+                return None;
+            }
+            Some(VariableMeta {
+                name: name.to_string(),
+                value_type: field(fields.next()),
+                writable: fields.next().map(str::trim) == Some("y"),
+                units: field(fields.next()),
+                description: field(fields.next()),
+            })
+        })
+        .collect()
+}
+
 pub struct XPlaneClient {
     socket: Option<UdpSocket>,
     address: String,
     cache: Arc<Mutex<HashMap<String, f64>>>,
-    subscriptions: HashMap<String, i32>,
+    /// RREF index and frequency by dataref; kept across reconnects
+    subscriptions: HashMap<String, (i32, i32)>,
+    datarefs: Vec<VariableMeta>,
+    /// `DataRefs.txt` to load on connect
+    datarefs_path: Option<PathBuf>,
 }
 
 impl XPlaneClient {
@@ -18,14 +52,44 @@ impl XPlaneClient {
             address: address.to_string(),
             cache: Arc::new(Mutex::new(HashMap::new())),
             subscriptions: HashMap::new(),
+            datarefs: Vec::new(),
+            datarefs_path: None,
         }
     }
 
+    /// Read the dataref list from `path` each time the client connects
+    pub fn with_datarefs_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.datarefs_path = Some(path.into());
+        self
+    }
+
+    /// Load the dataref list from `DataRefs.txt`; returns how many were found
+    pub fn load_datarefs(&mut self, path: &Path) -> Result<usize> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        self.datarefs = parse_datarefs_txt(&contents);
+        Ok(self.datarefs.len())
+    }
+
     pub fn subscribe(&mut self, variable: &str, frequency: i32) -> Result<()> {
-        if let Some(socket) = &self.socket {
-            let index = self.subscriptions.len() as i32 + 1;
-            self.subscriptions.insert(variable.to_string(), index);
+        if self.socket.is_none() {
+            return Err(anyhow!("Not connected"));
+        }
+        let index = self
+            .subscriptions
+            .get(variable)
+            .map_or(self.subscriptions.len() as i32 + 1, |&(index, _)| index);
+        self.subscriptions
+            .insert(variable.to_string(), (index, frequency));
+        self.send_rref(variable, frequency)
+    }
 
+    fn send_rref(&self, variable: &str, frequency: i32) -> Result<()> {
+        let index = self
+            .subscriptions
+            .get(variable)
+            .map_or(0, |&(index, _)| index);
+        if let Some(socket) = &self.socket {
             let mut buf = [0u8; 413];
             buf[0..4].copy_from_slice(b"RREF");
             buf[4] = 0;
@@ -45,14 +109,39 @@ impl XPlaneClient {
 }
 
 impl SimClient for XPlaneClient {
+    fn name(&self) -> &str {
+        "X-Plane"
+    }
+
     fn connect(&mut self) -> Result<()> {
         let socket = UdpSocket::bind("0.0.0.0:0")?;
         socket.set_nonblocking(true)?;
         self.socket = Some(socket);
+
+        // Resume what was subscribed before a disconnect
+        for (variable, &(_, frequency)) in &self.subscriptions {
+            self.send_rref(variable, frequency)?;
+        }
+
+        // Discovery is optional; a missing file shouldn't stop the connection
+        if let Some(path) = self.datarefs_path.clone() {
+            match self.load_datarefs(&path) {
+                Ok(count) => log::info!("Loaded {} datarefs from {}", count, path.display()),
+                Err(e) => log::warn!("{}", e),
+            }
+        }
         Ok(())
     }
 
     fn disconnect(&mut self) -> Result<()> {
+        // A frequency of 0 tells X-Plane to stop sending the dataref
+        let subscriptions: Vec<String> = self.subscriptions.keys().cloned().collect();
+        for variable in subscriptions {
+            if let Err(e) = self.send_rref(&variable, 0) {
+                log::warn!("Failed to unsubscribe {}: {}", variable, e);
+            }
+        }
+        self.cache.lock().unwrap().clear();
         self.socket = None;
         Ok(())
     }
@@ -124,7 +213,7 @@ impl SimClient for XPlaneClient {
                         if let Some(name) = self
                             .subscriptions
                             .iter()
-                            .find(|(_, &v)| v == index)
+                            .find(|(_, &(v, _))| v == index)
                             .map(|(k, _)| k.clone())
                         {
                             let mut cache = self.cache.lock().unwrap();
@@ -142,4 +231,105 @@ impl SimClient for XPlaneClient {
         let cache = self.cache.lock().unwrap();
         cache.clone()
     }
+
+    fn available_variables(&self) -> Vec<VariableMeta> {
+        self.datarefs.clone()
+    }
+
+    fn capabilities(&self) -> SimCapabilities {
+        SimCapabilities {
+            write_variables: true,
+            commands: true,
+            subscriptions: true,
+            string_variables: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities() {
+        let caps = XPlaneClient::new("127.0.0.1:49000").capabilities();
+        assert!(caps.write_variables);
+        assert!(caps.commands);
+        assert!(caps.subscriptions);
+        assert!(!caps.string_variables);
+    }
+
+    #[test]
+    fn test_reconnect_resubscribes() {
+        let sim = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sim.set_read_timeout(Some(std::time::Duration::from_secs(1)))
+            .unwrap();
+        let mut client = XPlaneClient::new(&sim.local_addr().unwrap().to_string());
+        let mut buf = [0u8; 512];
+        let mut next_rref = || {
+            let (n, _) = sim.recv_from(&mut buf).unwrap();
+            assert_eq!(&buf[0..4], b"RREF");
+            let frequency = i32::from_le_bytes(buf[5..9].try_into().unwrap());
+            let path = String::from_utf8_lossy(&buf[13..n - 1]).to_string();
+            (path, frequency)
+        };
+
+        client.connect().unwrap();
+        client.subscribe("sim/gear", 5).unwrap();
+        assert_eq!(next_rref(), ("sim/gear".to_string(), 5));
+
+        client.disconnect().unwrap();
+        assert_eq!(next_rref(), ("sim/gear".to_string(), 0));
+
+        client.connect().unwrap();
+        assert_eq!(next_rref(), ("sim/gear".to_string(), 5));
+    }
+
+    #[test]
+    fn test_parse_datarefs_txt() {
+        let datarefs = parse_datarefs_txt(
+            "2 1200 Mon Jan 15 10:00:00 2024\n\
+             sim/cockpit2/controls/gear_handle_down\tint\ty\tboolean\tGear handle position\n\
+             sim/flightmodel/engine/ENGN_RPM\tfloat[16]\tn\trpm\n\
+             \n\
+             sim/aircraft/view/acf_tailnum\tbyte[40]\ty\t???\tTail number\n",
+        );
+        assert_eq!(datarefs.len(), 3);
+        assert_eq!(
+            datarefs[0],
+            VariableMeta {
+                name: "sim/cockpit2/controls/gear_handle_down".to_string(),
+                value_type: Some("int".to_string()),
+                units: Some("boolean".to_string()),
+                writable: true,
+                description: Some("Gear handle position".to_string()),
+            }
+        );
+        assert!(!datarefs[1].writable);
+        assert_eq!(datarefs[1].description, None);
+        assert_eq!(datarefs[2].units, None);
+    }
+
+    #[test]
+    fn test_datarefs_file_loaded_on_connect() {
+        let path =
+            std::env::temp_dir().join(format!("openflite-datarefs-{}.txt", std::process::id()));
+        std::fs::write(
+            &path,
+            "2 1200 Mon Jan 15 10:00:00 2024\n\
+             sim/cockpit2/controls/gear_handle_down\tint\ty\tboolean\tGear handle position\n",
+        )
+        .unwrap();
+
+        let mut client = XPlaneClient::new("127.0.0.1:49000").with_datarefs_file(&path);
+        assert!(client.available_variables().is_empty());
+        client.connect().unwrap();
+        assert_eq!(client.available_variables().len(), 1);
+        std::fs::remove_file(&path).unwrap();
+
+        // A missing file leaves discovery empty but still connects
+        let mut client = XPlaneClient::new("127.0.0.1:49000").with_datarefs_file(&path);
+        client.connect().unwrap();
+        assert!(client.available_variables().is_empty());
+    }
 }
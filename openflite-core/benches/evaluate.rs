@@ -0,0 +1,32 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use openflite_core::config::MobiFlightProject;
+use openflite_core::mapping::MappingEngine;
+use std::collections::HashMap;
+
+#[path = "../src/synthetic.rs"]
+mod synthetic;
+
+fn project(count: usize, sources: usize) -> MobiFlightProject {
+    MobiFlightProject::load(&synthetic::large_config(count, sources)).unwrap()
+}
+
+fn evaluate(c: &mut Criterion) {
+    for (outputs, variables) in [(50, 20), (500, 200), (500, 2000)] {
+        let engine = MappingEngine::new(project(outputs, 200));
+        let data: HashMap<String, f64> = (0..variables)
+            .map(|i| (format!("var/{}", i), i as f64))
+            .collect();
+
+        let mut group = c.benchmark_group(format!("{}_outputs_{}_vars", outputs, variables));
+        group.bench_function("process_outputs", |b| {
+            b.iter(|| engine.process_outputs(black_box(&data)))
+        });
+        group.bench_function("evaluate_all", |b| {
+            b.iter(|| engine.evaluate_all(black_box(&data), &[]))
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, evaluate);
+criterion_main!(benches);
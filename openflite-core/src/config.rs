@@ -1,23 +1,45 @@
-use anyhow::Result;
-use quick_xml::de::from_str;
+use crate::error::{CoreError, Result};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "PascalCase")]
+#[serde(rename_all = "PascalCase", try_from = "RawProject")]
 pub struct MobiFlightProject {
     pub outputs: Outputs,
     pub inputs: Inputs,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawProject {
+    #[serde(alias = "outputs")]
+    outputs: Option<Outputs>,
+    #[serde(alias = "inputs")]
+    inputs: Option<Inputs>,
+}
+
+impl TryFrom<RawProject> for MobiFlightProject {
+    type Error = String;
+
+    fn try_from(raw: RawProject) -> std::result::Result<Self, Self::Error> {
+        if raw.outputs.is_none() && raw.inputs.is_none() {
+            return Err("no <Outputs> or <Inputs> element in project".to_string());
+        }
+        Ok(Self {
+            outputs: raw.outputs.unwrap_or_default(),
+            inputs: raw.inputs.unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Outputs {
-    #[serde(rename = "Config", default)]
+    #[serde(rename = "Config", alias = "config", default)]
     pub config: Vec<OutputConfig>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Inputs {
-    #[serde(rename = "Config", default)]
+    #[serde(rename = "Config", alias = "config", default)]
     pub config: Vec<InputConfig>,
 }
 
@@ -26,9 +48,12 @@ pub struct Inputs {
 pub struct OutputConfig {
     #[serde(rename = "@guid")]
     pub guid: String,
-    #[serde(rename = "@active")]
+    /// An attribute in OpenFlite files, an `<active>` child in MobiFlight exports
+    #[serde(rename = "@active", alias = "active", alias = "Active")]
     pub active: bool,
+    #[serde(default, alias = "description")]
     pub description: String,
+    #[serde(alias = "settings")]
     pub settings: ConfigSettings,
 }
 
@@ -37,95 +62,342 @@ pub struct OutputConfig {
 pub struct InputConfig {
     #[serde(rename = "@guid")]
     pub guid: String,
-    #[serde(rename = "@active")]
+    #[serde(rename = "@active", alias = "active", alias = "Active")]
     pub active: bool,
+    /// Input identifier the firmware reports; falls back to `description`
+    #[serde(rename = "@name", default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Only match events from the board with this serial
+    #[serde(rename = "@serial", default, skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+    #[serde(default, alias = "description")]
     pub description: String,
+    #[serde(alias = "settings")]
     pub settings: InputSettings,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct InputSettings {
+    /// Where MobiFlight exports put the input name; `InputConfig::name` wins
+    #[serde(rename = "@name", default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Where MobiFlight exports put the board serial; `InputConfig::serial` wins
+    #[serde(rename = "@serial", default, skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+    #[serde(alias = "button", skip_serializing_if = "Option::is_none")]
     pub button: Option<ButtonAction>,
+    #[serde(alias = "encoder", skip_serializing_if = "Option::is_none")]
     pub encoder: Option<EncoderAction>,
+    #[serde(alias = "selector", skip_serializing_if = "Option::is_none")]
+    pub selector: Option<SelectorAction>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ButtonAction {
+    #[serde(alias = "onPress", skip_serializing_if = "Option::is_none")]
     pub on_press: Option<Action>,
+    #[serde(alias = "onRelease", skip_serializing_if = "Option::is_none")]
     pub on_release: Option<Action>,
+    /// Fire `on_press` again every this many milliseconds while held
+    #[serde(rename = "@repeat", default, skip_serializing_if = "Option::is_none")]
+    pub repeat: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct EncoderAction {
+    #[serde(alias = "onLeft", skip_serializing_if = "Option::is_none")]
     pub on_left: Option<Action>,
+    #[serde(alias = "onRight", skip_serializing_if = "Option::is_none")]
     pub on_right: Option<Action>,
+    /// Fast turns; `on_left` / `on_right` are used when these are missing
+    #[serde(alias = "onLeftFast", skip_serializing_if = "Option::is_none")]
+    pub on_left_fast: Option<Action>,
+    #[serde(alias = "onRightFast", skip_serializing_if = "Option::is_none")]
+    pub on_right_fast: Option<Action>,
+    /// Events the encoder sends per physical detent; one action fires per detent
+    #[serde(
+        rename = "@stepsPerDetent",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub steps_per_detent: Option<u32>,
+    /// Swap left and right, for encoders wired the other way round
+    #[serde(rename = "@reverse", default)]
+    pub reverse: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SelectorAction {
+    #[serde(rename = "Position", default)]
+    pub positions: Vec<SelectorPosition>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SelectorPosition {
+    #[serde(rename = "@value")]
+    pub value: String, // position reported by the hardware
+    pub action: Action,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Action {
-    #[serde(rename = "@type")]
-    pub action_type: String, // e.g., "XplaneAction"
-    #[serde(rename = "@cmd")]
+    #[serde(rename = "@type", default)]
+    pub action_type: String, // e.g., "XplaneAction", "DatarefDelta", "ToggleDataref"
+    #[serde(rename = "@cmd", skip_serializing_if = "Option::is_none")]
     pub command: Option<String>,
-    #[serde(rename = "@dataref")]
+    #[serde(rename = "@dataref", skip_serializing_if = "Option::is_none")]
     pub dataref: Option<String>,
-    #[serde(rename = "@value")]
+    #[serde(rename = "@value", skip_serializing_if = "Option::is_none")]
     pub value: Option<String>,
+    /// `DatarefDelta`: amount added per event (negative to decrement)
+    #[serde(rename = "@step", default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<f64>,
+    /// `DatarefDelta`: lower bound
+    #[serde(rename = "@min", default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    /// `DatarefDelta`: upper bound
+    #[serde(rename = "@max", default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    /// `DatarefDelta`: past a bound, continue from the other one instead of clamping
+    #[serde(rename = "@wrap", default)]
+    pub wrap: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ConfigSettings {
+    #[serde(alias = "source", skip_serializing_if = "Option::is_none")]
     pub source: Option<Source>,
+    #[serde(alias = "comparison", skip_serializing_if = "Option::is_none")]
     pub comparison: Option<Comparison>,
+    #[serde(alias = "display", skip_serializing_if = "Option::is_none")]
     pub display: Option<Display>,
+    #[serde(alias = "calibration", skip_serializing_if = "Option::is_none")]
+    pub calibration: Option<Calibration>,
+}
+
+/// Measured (value -> raw position) pairs for a servo or stepper gauge
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Calibration {
+    #[serde(rename = "Point", default)]
+    pub points: Vec<CalibrationPoint>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CalibrationPoint {
+    /// Sim value
+    #[serde(rename = "@value")]
+    pub value: f64,
+    /// Raw servo PWM / stepper position where the needle shows `value`
+    #[serde(rename = "@raw")]
+    pub raw: f64,
+}
+
+impl Calibration {
+    /// Interpolate between the points around `value`, clamped to the end points
+    pub fn interpolate(&self, value: f64) -> f64 {
+        let mut points = self.points.clone();
+        points.sort_by(|a, b| a.value.total_cmp(&b.value));
+
+        let (Some(first), Some(last)) = (points.first(), points.last()) else {
+            return value;
+        };
+        if value <= first.value {
+            return first.raw;
+        }
+        if value >= last.value {
+            return last.raw;
+        }
+
+        for pair in points.windows(2) {
+            let (lo, hi) = (pair[0], pair[1]);
+            if value <= hi.value {
+                let t = (value - lo.value) / (hi.value - lo.value);
+                return lo.raw + t * (hi.raw - lo.raw);
+            }
+        }
+        last.raw
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Source {
-    #[serde(rename = "@type")]
+    #[serde(rename = "@type", default)]
     pub source_type: String,
-    #[serde(rename = "@name")]
+    /// MobiFlight exports name X-Plane datarefs in `path`
+    #[serde(rename = "@name", alias = "@path", default)]
     pub name: String,
+    /// `String` for text variables such as the ATC callsign; numeric if unset
+    #[serde(
+        rename = "@valueType",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub value_type: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Comparison {
-    #[serde(rename = "@active")]
+    #[serde(rename = "@active", default)]
     pub active: bool,
-    #[serde(rename = "@value")]
+    #[serde(rename = "@value", default)]
     pub value: String,
-    #[serde(rename = "@operand")]
+    #[serde(rename = "@operand", default)]
     pub operand: String,
-    #[serde(rename = "@ifValue")]
+    #[serde(rename = "@ifValue", default)]
     pub if_value: String,
-    #[serde(rename = "@elseValue")]
+    #[serde(rename = "@elseValue", default)]
     pub else_value: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Display {
     #[serde(rename = "@type")]
     pub display_type: String,
-    #[serde(rename = "@serial")]
+    #[serde(rename = "@serial", default)]
     pub serial: String,
-    #[serde(rename = "@trigger")]
+    #[serde(rename = "@trigger", default)]
     pub trigger: String,
-    #[serde(rename = "@pin")]
+    #[serde(rename = "@pin", default)]
     pub pin: String,
+    /// Number of digits to render into (7Segment / LCD)
+    #[serde(rename = "@width", default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<usize>,
+    /// Fixed number of decimal places; defaults to 0
+    #[serde(rename = "@decimals", default, skip_serializing_if = "Option::is_none")]
+    pub decimals: Option<usize>,
+    /// Character used to pad up to `width`; defaults to a space
+    #[serde(rename = "@padChar", default, skip_serializing_if = "Option::is_none")]
+    pub pad_char: Option<char>,
+    /// Pad with zeros after the sign instead of `pad_char`
+    #[serde(rename = "@leadingZeros", default)]
+    pub leading_zeros: bool,
+    /// I2C address of an LCD backpack, decimal or `0x`-prefixed hex
+    #[serde(rename = "@address", default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// LCDBar: value shown as an empty bar
+    #[serde(rename = "@min", default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    /// LCDBar: value shown as a full bar
+    #[serde(rename = "@max", default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    /// Warning: milliseconds to blink before going solid; defaults to 5000
+    #[serde(
+        rename = "@blinkDuration",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub blink_duration: Option<u64>,
+    /// Warning: milliseconds a new condition must hold; defaults to 200
+    #[serde(rename = "@debounce", default, skip_serializing_if = "Option::is_none")]
+    pub debounce: Option<u64>,
+    /// LCD / 7Segment: labels shown instead of specific values
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_map: Option<ValueMap>,
+}
+
+/// Lookup table from sim values to display text, e.g. autopilot mode 1 -> "HDG"
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ValueMap {
+    /// Shown for values without an entry; without it they render as numbers
+    #[serde(rename = "@default", default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(rename = "Entry", default)]
+    pub entries: Vec<ValueMapEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValueMapEntry {
+    #[serde(rename = "@value")]
+    pub value: f64,
+    #[serde(rename = "@label")]
+    pub label: String,
+}
+
+impl ValueMap {
+    /// The label for `value`, falling back to the default label
+    pub fn label(&self, value: f64) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|e| (e.value - value).abs() < f64::EPSILON)
+            .map(|e| e.label.as_str())
+            .or(self.default.as_deref())
+    }
+}
+
+impl InputConfig {
+    /// Whether an event for input `name` on board `serial` belongs to this config
+    pub fn matches(&self, serial: &str, name: &str) -> bool {
+        let input_name = self
+            .name
+            .as_deref()
+            .or(self.settings.name.as_deref())
+            .unwrap_or(&self.description);
+        let input_serial = self.serial.as_deref().or(self.settings.serial.as_deref());
+        input_name == name && input_serial.is_none_or(|s| s == serial)
+    }
 }
 
+/// Small config for trying OpenFlite without hardware
+pub const DEMO_CONFIG: &str = r#"
+    <MobiFlightProject>
+        <Outputs>
+            <Config guid="demo-altitude" active="true">
+                <Description>Altitude LED</Description>
+                <Settings>
+                    <Source type="SimConnect" name="sim/flightmodel/position/altitude" />
+                    <Comparison active="true" value="1050" operand=">" ifValue="1" elseValue="0" />
+                    <Display type="Pin" serial="DEMO-BOARD" trigger="OnChange" pin="13" />
+                </Settings>
+            </Config>
+        </Outputs>
+        <Inputs>
+            <Config guid="demo-gear" active="true">
+                <Description>GearToggle</Description>
+                <Settings>
+                    <Button>
+                        <OnPress type="XplaneAction" cmd="sim/annunciator/gear_unsafe" />
+                    </Button>
+                </Settings>
+            </Config>
+            <Config guid="demo-heading" active="true">
+                <Description>HeadingDial</Description>
+                <Settings>
+                    <Encoder>
+                        <OnLeft type="XplaneAction" cmd="sim/autopilot/heading_down" />
+                        <OnRight type="XplaneAction" cmd="sim/autopilot/heading_up" />
+                    </Encoder>
+                </Settings>
+            </Config>
+        </Inputs>
+    </MobiFlightProject>
+"#;
+
 impl MobiFlightProject {
+    /// Parse a project, skipping elements and attributes OpenFlite doesn't use
     pub fn load(xml_content: &str) -> Result<Self> {
-        let project: MobiFlightProject = from_str(xml_content)?;
-        Ok(project)
+        let mut deserializer = quick_xml::de::Deserializer::from_str(xml_content);
+        serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+            let path = e.path().to_string();
+            if path == "." {
+                CoreError::ConfigParse(e.inner().to_string())
+            } else {
+                CoreError::ConfigParse(format!("{} (at {})", e.inner(), path))
+            }
+        })
+    }
+
+    /// Write the project back out as OpenFlite XML, which `load` reads
+    pub fn to_xml(&self) -> Result<String> {
+        quick_xml::se::to_string(self).map_err(|e| CoreError::ConfigSave(e.to_string()))
     }
 }
 
@@ -158,4 +430,178 @@ mod tests {
         assert_eq!(project.outputs.config.len(), 1);
         assert_eq!(project.outputs.config[0].description, "Test Output");
     }
+
+    #[test]
+    fn test_parse_selector() {
+        let xml = r#"
+            <MobiFlightProject>
+                <Outputs></Outputs>
+                <Inputs>
+                    <Config guid="nav-source" active="true">
+                        <Description>NavSource</Description>
+                        <Settings>
+                            <Selector>
+                                <Position value="0">
+                                    <Action type="XplaneAction" cmd="sim/gps/nav_source_nav1" />
+                                </Position>
+                                <Position value="1">
+                                    <Action type="XplaneAction" cmd="sim/gps/nav_source_gps" />
+                                </Position>
+                            </Selector>
+                        </Settings>
+                    </Config>
+                </Inputs>
+            </MobiFlightProject>
+        "#;
+        let project = MobiFlightProject::load(xml).unwrap();
+        let selector = project.inputs.config[0].settings.selector.as_ref().unwrap();
+        assert_eq!(selector.positions.len(), 2);
+        assert_eq!(selector.positions[1].value, "1");
+        assert_eq!(
+            selector.positions[1].action.command.as_deref(),
+            Some("sim/gps/nav_source_gps")
+        );
+    }
+
+    /// A MobiFlight Connector .mcc export
+    const MOBIFLIGHT_EXPORT: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<MobiflightConnector>
+  <outputs>
+    <config guid="8d5b5e3a-1c4f-4b7e-9a55-0f3f6c1d2e01">
+      <active>true</active>
+      <description>Gear down LED</description>
+      <settings msdata:InstanceType="MobiFlight.OutputConfigItem, MFConnector, Version=10.3.0.2, Culture=neutral, PublicKeyToken=null" xmlns:msdata="urn:schemas-microsoft-com:xml-msdata">
+        <source type="XplaneDataRef" path="sim/cockpit2/controls/gear_handle_down" />
+        <comparison active="False" value="" operand="" ifValue="" elseValue="" />
+        <display type="Pin" serial="SN-3f1" trigger="normal" pin="13" pinBrightness="255" pinPwm="False" />
+        <interpolation active="False" />
+        <preconditions />
+        <configrefs />
+      </settings>
+    </config>
+    <config guid="0b8f2c6e-52d1-4f0a-8e43-7a9c1b2d3e02">
+      <active>false</active>
+      <description>Unused FSUIPC output</description>
+      <settings msdata:InstanceType="MobiFlight.OutputConfigItem, MFConnector, Version=10.3.0.2, Culture=neutral, PublicKeyToken=null" xmlns:msdata="urn:schemas-microsoft-com:xml-msdata">
+        <source type="FSUIPC" offset="0x0BEC" offsetType="Integer" size="4" mask="0xFFFFFFFF" bcdMode="False" />
+        <comparison active="True" value="0" operand="&gt;" ifValue="1" elseValue="0" />
+        <display type="Pin" serial="SN-3f1" trigger="normal" pin="7" pinBrightness="255" pinPwm="False" />
+        <preconditions>
+          <precondition type="none" active="false" ref="" operand="=" value="" logic="and" />
+        </preconditions>
+        <configrefs />
+      </settings>
+    </config>
+  </outputs>
+  <inputs>
+    <config guid="1f0a6d3b-9e2c-4a7f-b1d5-3c8e0f6a9b03">
+      <active>true</active>
+      <description>Gear lever</description>
+      <settings msdata:InstanceType="MobiFlight.InputConfigItem, MFConnector, Version=10.3.0.2, Culture=neutral, PublicKeyToken=null" serial="SN-3f1" name="Button 1" type="Button" xmlns:msdata="urn:schemas-microsoft-com:xml-msdata">
+        <button>
+          <onPress type="XplaneAction" cmd="sim/flight_controls/landing_gear_toggle" />
+          <onRelease />
+        </button>
+        <preconditions />
+        <configrefs />
+      </settings>
+    </config>
+    <config guid="2e1b7c4d-0f3a-4b6e-9d2c-5a8f1e7b0c04">
+      <active>true</active>
+      <description>Heading bug</description>
+      <settings msdata:InstanceType="MobiFlight.InputConfigItem, MFConnector, Version=10.3.0.2, Culture=neutral, PublicKeyToken=null" serial="SN-3f1" name="Encoder 1" type="Encoder" xmlns:msdata="urn:schemas-microsoft-com:xml-msdata">
+        <encoder>
+          <onLeft type="XplaneAction" cmd="sim/autopilot/heading_down" />
+          <onLeftFast type="XplaneAction" cmd="sim/autopilot/heading_down_fast" />
+          <onRight type="XplaneAction" cmd="sim/autopilot/heading_up" />
+          <onRightFast type="XplaneAction" cmd="sim/autopilot/heading_up_fast" />
+        </encoder>
+        <preconditions />
+        <configrefs />
+      </settings>
+    </config>
+  </inputs>
+</MobiflightConnector>
+"#;
+
+    #[test]
+    fn test_parse_mobiflight_export() {
+        let project = MobiFlightProject::load(MOBIFLIGHT_EXPORT).unwrap();
+
+        let outputs = &project.outputs.config;
+        assert_eq!(outputs.len(), 2);
+        assert!(outputs[0].active);
+        assert!(!outputs[1].active);
+        assert_eq!(outputs[0].description, "Gear down LED");
+        let source = outputs[0].settings.source.as_ref().unwrap();
+        assert_eq!(source.name, "sim/cockpit2/controls/gear_handle_down");
+        let comparison = outputs[1].settings.comparison.as_ref().unwrap();
+        assert!(comparison.active);
+        assert_eq!(comparison.operand, ">");
+        let display = outputs[0].settings.display.as_ref().unwrap();
+        assert_eq!(display.display_type, "Pin");
+        assert_eq!(display.pin, "13");
+        assert_eq!(outputs[1].settings.source.as_ref().unwrap().name, "");
+
+        let button = &project.inputs.config[0];
+        assert!(button.active);
+        assert!(button.matches("SN-3f1", "Button 1"));
+        assert!(!button.matches("SN-other", "Button 1"));
+        let actions = button.settings.button.as_ref().unwrap();
+        assert_eq!(
+            actions.on_press.as_ref().unwrap().command.as_deref(),
+            Some("sim/flight_controls/landing_gear_toggle")
+        );
+        assert_eq!(actions.on_release.as_ref().unwrap().action_type, "");
+
+        let encoder = project.inputs.config[1].settings.encoder.as_ref().unwrap();
+        assert_eq!(
+            encoder.on_right_fast.as_ref().unwrap().command.as_deref(),
+            Some("sim/autopilot/heading_up_fast")
+        );
+        assert!(encoder.on_left.is_some());
+    }
+
+    #[test]
+    fn test_saved_project_loads_back() {
+        let project = MobiFlightProject::load(MOBIFLIGHT_EXPORT).unwrap();
+        let reloaded = MobiFlightProject::load(&project.to_xml().unwrap()).unwrap();
+        assert_eq!(format!("{:?}", reloaded), format!("{:?}", project));
+    }
+
+    #[test]
+    fn test_unrelated_xml_is_rejected() {
+        for xml in ["<Foo><Bar/></Foo>", "<MobiFlightProject />"] {
+            assert!(matches!(
+                MobiFlightProject::load(xml),
+                Err(CoreError::ConfigParse(msg)) if msg.contains("<Outputs> or <Inputs>")
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_error_names_element() {
+        let xml = r#"
+            <MobiFlightProject>
+                <Outputs>
+                    <Config guid="ok" active="true">
+                        <Description>Fine</Description>
+                        <Settings />
+                    </Config>
+                    <Config guid="bad" active="true">
+                        <Description>Bad width</Description>
+                        <Settings>
+                            <Display type="7Segment" serial="SN-1" pin="0" width="wide" />
+                        </Settings>
+                    </Config>
+                </Outputs>
+            </MobiFlightProject>
+        "#;
+        let err = MobiFlightProject::load(xml).unwrap_err().to_string();
+        assert!(
+            err.ends_with("(at Outputs.Config[1].Settings.Display.@width)"),
+            "{}",
+            err
+        );
+    }
 }
@@ -1,29 +1,95 @@
-use crate::protocol::{Command, Response};
-use anyhow::{anyhow, Result};
+use crate::error::{CoreError, Result};
+use crate::protocol::{Command, DeviceStatus, Response};
 use serialport::SerialPort;
-use std::io::{BufRead, BufReader, Write};
-use std::time::Duration;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::time::{Duration, Instant};
 
+const INFO_TIMEOUT: Duration = Duration::from_millis(500);
+const POLL_TIMEOUT: Duration = Duration::from_millis(2);
+
+/// Byte stream to a board (a serial port outside of tests)
+pub trait Transport: Read + Write + Send {
+    /// Bytes waiting to be read; only a hint, some drivers report 0
+    fn bytes_to_read(&self) -> io::Result<u32>;
+
+    /// How long a read waits for data before failing with `TimedOut`
+    fn set_timeout(&mut self, _timeout: Duration) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for Box<dyn SerialPort> {
+    fn bytes_to_read(&self) -> io::Result<u32> {
+        SerialPort::bytes_to_read(self.as_ref()).map_err(io::Error::from)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        SerialPort::set_timeout(self.as_mut(), timeout).map_err(io::Error::from)
+    }
+}
+
+/// Snapshot of a board's identity and state, for display or lookups
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub board_type: String,
+    pub serial: String,
+    pub version: String,
+    pub port: Option<String>,
+    pub connected: bool,
+}
+
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.board_type)
+    }
+}
+
+/// A connected MobiFlight board; each frame goes out in a single write
 pub struct MobiFlightDevice {
-    port: Box<dyn SerialPort>,
+    port: Box<dyn Transport>,
     pub name: String,
     pub board_type: String,
     pub serial: String,
     pub version: String,
+    /// Serial port the board was opened on; `None` for caller-supplied transports
+    pub port_name: Option<String>,
+    /// Cleared once a write fails or heartbeats go unanswered
+    pub connected: bool,
+    last_write: Instant,
+    awaiting_info: Option<Instant>,
+    rx_buf: Vec<u8>,
+    pending: Vec<Response>,
+    read_timeout: Duration,
 }
 
 impl MobiFlightDevice {
     pub fn new(port_name: &str) -> Result<Self> {
         let port = serialport::new(port_name, 115200)
-            .timeout(Duration::from_millis(500))
+            .timeout(INFO_TIMEOUT)
             .open()?;
 
+        let mut dev = Self::with_transport(Box::new(port))?;
+        dev.port_name = Some(port_name.to_string());
+        Ok(dev)
+    }
+
+    /// Talk to a board over an already-open transport
+    pub fn with_transport(port: Box<dyn Transport>) -> Result<Self> {
         let mut dev = Self {
             port,
             name: "Unknown".to_string(),
             board_type: "Unknown".to_string(),
             serial: "Unknown".to_string(),
             version: "Unknown".to_string(),
+            port_name: None,
+            connected: true,
+            last_write: Instant::now(),
+            awaiting_info: None,
+            rx_buf: Vec::new(),
+            pending: Vec::new(),
+            read_timeout: INFO_TIMEOUT,
         };
 
         dev.update_info()?;
@@ -32,6 +98,7 @@ impl MobiFlightDevice {
     }
 
     pub fn update_info(&mut self) -> Result<()> {
+        self.set_read_timeout(INFO_TIMEOUT)?;
         self.send_command(Command::GetInfo)?;
 
         let mut reader = BufReader::new(&mut self.port);
@@ -51,17 +118,99 @@ impl MobiFlightDevice {
             self.version = version;
             Ok(())
         } else {
-            Err(anyhow!("Failed to parse info response: {}", line))
+            Err(CoreError::Protocol(format!(
+                "Failed to parse info response: {}",
+                line
+            )))
         }
     }
 
     pub fn send_command(&mut self, cmd: Command) -> Result<()> {
+        if !self.connected {
+            return Err(CoreError::NotConnected);
+        }
         let serialized = cmd.serialize();
         self.port.write_all(serialized.as_bytes())?;
         self.port.flush()?;
+        self.last_write = Instant::now();
+        Ok(())
+    }
+
+    /// Write several frames with a single write and flush
+    pub fn send_commands(&mut self, cmds: &[Command]) -> Result<()> {
+        if cmds.is_empty() {
+            return Ok(());
+        }
+        if !self.connected {
+            return Err(CoreError::NotConnected);
+        }
+        let serialized: String = cmds.iter().map(Command::serialize).collect();
+        self.port.write_all(serialized.as_bytes())?;
+        self.port.flush()?;
+        self.last_write = Instant::now();
         Ok(())
     }
 
+    /// Ask the board for its free RAM, config checksum and uptime
+    pub fn read_status(&mut self) -> Result<DeviceStatus> {
+        self.set_read_timeout(INFO_TIMEOUT)?;
+        self.send_command(Command::GetStatus)?;
+
+        let deadline = Instant::now() + INFO_TIMEOUT;
+        let mut chunk = [0u8; 256];
+        loop {
+            while let Some(end) = self.rx_buf.iter().position(|&b| b == b';') {
+                let frame: Vec<u8> = self.rx_buf.drain(..=end).collect();
+                match Response::parse(&String::from_utf8_lossy(&frame)) {
+                    Some(Response::Status(status)) => return Ok(status),
+                    Some(other) => self.pending.push(other),
+                    None => {}
+                }
+            }
+            if Instant::now() >= deadline {
+                return Err(CoreError::Protocol(format!(
+                    "No status response from {}",
+                    self.serial
+                )));
+            }
+
+            match self.port.read(&mut chunk) {
+                // End of stream: the port is gone, no answer is coming
+                Ok(0) => {
+                    return Err(CoreError::Protocol(format!(
+                        "{} closed the connection",
+                        self.serial
+                    )))
+                }
+                Ok(n) => self.rx_buf.extend_from_slice(&chunk[..n]),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::TimedOut
+                            | io::ErrorKind::WouldBlock
+                            | io::ErrorKind::Interrupted
+                    ) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    pub fn info(&self) -> DeviceInfo {
+        DeviceInfo {
+            name: self.name.clone(),
+            board_type: self.board_type.clone(),
+            serial: self.serial.clone(),
+            version: self.version.clone(),
+            port: self.port_name.clone(),
+            connected: self.connected,
+        }
+    }
+
+    /// Time since the last successful write
+    pub fn idle_time(&self) -> Duration {
+        self.last_write.elapsed()
+    }
+
     pub fn set_pin(&mut self, pin: u8, value: u8) -> Result<()> {
         self.send_command(Command::SetPin(pin, value))
     }
@@ -74,6 +223,11 @@ impl MobiFlightDevice {
         self.send_command(Command::SetLCD(display_id, line, text.to_string()))
     }
 
+    /// Define one of the LCD's eight custom characters
+    pub fn set_lcd_custom_char(&mut self, slot: u8, bitmap: [u8; 8]) -> Result<()> {
+        self.send_command(Command::SetLcdCustomChar(slot, bitmap))
+    }
+
     pub fn set_stepper(&mut self, motor_id: u8, steps: i32) -> Result<()> {
         self.send_command(Command::SetStepper(motor_id, steps))
     }
@@ -82,28 +236,243 @@ impl MobiFlightDevice {
         self.send_command(Command::SetRGB(led_id, r, g, b))
     }
 
+    /// Move a servo to a raw pulse width, bypassing any value mapping
+    pub fn set_servo_raw(&mut self, servo_id: u8, pwm: u16) -> Result<()> {
+        self.send_command(Command::SetServo(servo_id, pwm))
+    }
+
+    /// Move a stepper to a raw step position, bypassing any value mapping
+    pub fn set_stepper_raw(&mut self, motor_id: u8, steps: i32) -> Result<()> {
+        self.send_command(Command::SetStepper(motor_id, steps))
+    }
+
+    /// Send a heartbeat; `poll_events` notes the board's answer
+    pub(crate) fn ping(&mut self) -> Result<()> {
+        self.send_command(Command::GetInfo)?;
+        self.awaiting_info.get_or_insert_with(Instant::now);
+        Ok(())
+    }
+
+    /// How long the board has left a heartbeat unanswered, if it has
+    pub(crate) fn unanswered_for(&self) -> Option<Duration> {
+        self.awaiting_info.map(|sent| sent.elapsed())
+    }
+
+    /// List serial port names, retrying once if enumeration fails
     pub fn scan() -> Result<Vec<String>> {
-        let ports = serialport::available_ports()?;
-        Ok(ports.into_iter().map(|p| p.port_name).collect())
+        let ports = serialport::available_ports().or_else(|e| {
+            log::debug!("Serial port enumeration failed ({}), retrying", e);
+            std::thread::sleep(Duration::from_millis(200));
+            serialport::available_ports()
+        });
+
+        match ports {
+            Ok(ports) => Ok(ports.into_iter().map(|p| p.port_name).collect()),
+            // Nothing to enumerate is an empty result, not a failure
+            Err(e) if e.kind() == serialport::ErrorKind::NoDevice => Ok(Vec::new()),
+            Err(e) => Err(scan_error(&e)),
+        }
     }
 
+    /// Read whatever the board has sent and return the complete frames
     pub fn poll_events(&mut self) -> Vec<Response> {
-        let mut responses = Vec::new();
-        if let Ok(count) = self.port.bytes_to_read() {
-            if count > 0 {
-                let mut reader = BufReader::new(&mut self.port);
-                let mut line = String::new();
-                // Read everything available until we hit a delimiter (;)
-                // Simplification for now: read one line if available
-                if let Ok(n) = reader.read_line(&mut line) {
-                    if n > 0 {
-                        if let Some(resp) = Response::parse(&line) {
-                            responses.push(resp);
-                        }
+        if let Err(e) = self.set_read_timeout(POLL_TIMEOUT) {
+            log::debug!("Failed to set poll timeout on {}: {}", self.serial, e);
+        }
+
+        let mut chunk = [0u8; 256];
+        loop {
+            match self.port.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => {
+                    self.rx_buf.extend_from_slice(&chunk[..n]);
+                    if n < chunk.len() {
+                        break;
                     }
                 }
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                    ) =>
+                {
+                    break
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    log::debug!("Read from {} failed: {}", self.serial, e);
+                    break;
+                }
+            }
+        }
+
+        let mut responses = std::mem::take(&mut self.pending);
+        while let Some(end) = self.rx_buf.iter().position(|&b| b == b';') {
+            let frame: Vec<u8> = self.rx_buf.drain(..=end).collect();
+            if let Some(resp) = Response::parse(&String::from_utf8_lossy(&frame)) {
+                responses.push(resp);
             }
         }
+        if responses.iter().any(|r| matches!(r, Response::Info { .. })) {
+            self.awaiting_info = None;
+        }
         responses
     }
+
+    fn set_read_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        if self.read_timeout != timeout {
+            self.port.set_timeout(timeout)?;
+            self.read_timeout = timeout;
+        }
+        Ok(())
+    }
+}
+
+fn scan_error(e: &serialport::Error) -> CoreError {
+    let permission_denied = e.kind() == serialport::ErrorKind::Io(io::ErrorKind::PermissionDenied)
+        || e.to_string().to_lowercase().contains("permission denied");
+
+    let message = if permission_denied && cfg!(target_os = "linux") {
+        "Permission denied on /dev/tty* - add your user to the dialout group \
+         (sudo usermod -aG dialout $USER) and log in again"
+            .to_string()
+    } else if permission_denied {
+        "Permission denied while listing serial ports".to_string()
+    } else {
+        format!("Could not list serial ports: {}", e)
+    };
+    CoreError::PortEnumeration(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockTransport;
+    use serialport::ErrorKind;
+
+    #[test]
+    fn test_poll_reads_when_bytes_to_read_is_zero() {
+        let (mut dev, mock) = MockTransport::device("Panel", "SN-1");
+        mock.set_hide_pending(true);
+        assert!(dev.poll_events().is_empty());
+
+        mock.push_incoming("11,Encoder1,1;\r\n11,Encoder1,1;\r\n11,Button");
+        let events = dev.poll_events();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            &events[0],
+            Response::InputEvent { name, value } if name == "Encoder1" && value == "1"
+        ));
+
+        // The rest of a split frame completes on the next poll
+        mock.push_incoming("1,0;\r\n");
+        assert!(matches!(
+            dev.poll_events().as_slice(),
+            [Response::InputEvent { name, value }] if name == "Button1" && value == "0"
+        ));
+    }
+
+    #[test]
+    fn test_read_status_keeps_earlier_events() {
+        let (mut dev, mock) = MockTransport::device("Panel", "SN-1");
+        mock.clear_written();
+        mock.push_incoming("11,Button1,1;\r\n21,1834,0x5A3C,86400123;\r\n");
+
+        let status = dev.read_status().unwrap();
+        assert_eq!(mock.written(), "21;");
+        assert_eq!(status.free_ram, 1834);
+        assert_eq!(status.config_crc, 0x5A3C);
+        assert_eq!(status.uptime, Duration::from_millis(86_400_123));
+
+        assert!(matches!(
+            dev.poll_events().as_slice(),
+            [Response::InputEvent { name, value }] if name == "Button1" && value == "1"
+        ));
+
+        // Firmware without GetStatus never answers
+        assert!(matches!(dev.read_status(), Err(CoreError::Protocol(_))));
+
+        // A closed port gives up at once rather than spinning to the deadline
+        mock.close_reads();
+        let started = std::time::Instant::now();
+        assert!(matches!(
+            dev.read_status(),
+            Err(CoreError::Protocol(msg)) if msg.contains("closed")
+        ));
+        assert!(started.elapsed() < INFO_TIMEOUT);
+    }
+
+    #[test]
+    fn test_send_commands_flushes_once() {
+        let (mut dev, mock) = MockTransport::device("Panel", "SN-1");
+        let digits: Vec<Command> = (0..6)
+            .map(|i| Command::Set7Segment(0, i, i.to_string()))
+            .collect();
+
+        dev.send_commands(&digits).unwrap();
+
+        assert_eq!(mock.flushes(), 1);
+        assert_eq!(
+            mock.written(),
+            "15,0,0,0;15,0,1,1;15,0,2,2;15,0,3,3;15,0,4,4;15,0,5,5;"
+        );
+
+        dev.send_commands(&[]).unwrap();
+        assert_eq!(mock.flushes(), 1);
+    }
+
+    #[test]
+    fn test_set_lcd_custom_char_writes_one_frame() {
+        let (mut dev, mock) = MockTransport::device("Panel", "SN-1");
+        mock.clear_written();
+        let bitmap = [0x10; 8];
+
+        dev.set_lcd_custom_char(1, bitmap).unwrap();
+
+        assert_eq!(
+            mock.written(),
+            Command::SetLcdCustomChar(1, bitmap).serialize()
+        );
+    }
+
+    #[test]
+    fn test_raw_moves() {
+        let (mut dev, mock) = MockTransport::device("Panel", "SN-1");
+        mock.clear_written();
+
+        dev.set_servo_raw(2, 1500).unwrap();
+        dev.set_stepper_raw(0, -40).unwrap();
+
+        assert_eq!(mock.written(), "20,2,1500;17,0,-40;");
+    }
+
+    #[test]
+    fn test_scan_error_classification() {
+        let denied = serialport::Error::new(
+            ErrorKind::Io(io::ErrorKind::PermissionDenied),
+            "Permission denied",
+        );
+        match scan_error(&denied) {
+            CoreError::PortEnumeration(msg) => {
+                assert!(msg.starts_with("Permission denied"));
+                if cfg!(target_os = "linux") {
+                    assert!(msg.contains("dialout"));
+                }
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        // udev reports permission problems as Unknown with only a message
+        let udev = serialport::Error::new(ErrorKind::Unknown, "udev: Permission denied");
+        assert!(matches!(
+            scan_error(&udev),
+            CoreError::PortEnumeration(msg) if msg.starts_with("Permission denied")
+        ));
+
+        let other = serialport::Error::new(ErrorKind::Unknown, "udev enumerate failed");
+        assert!(matches!(
+            scan_error(&other),
+            CoreError::PortEnumeration(msg) if msg == "Could not list serial ports: udev enumerate failed"
+        ));
+    }
 }
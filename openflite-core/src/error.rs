@@ -0,0 +1,73 @@
+use thiserror::Error;
+
+/// Errors returned by the public `openflite-core` API
+#[derive(Debug, Error)]
+pub enum CoreError {
+    /// No connected device has the requested serial
+    #[error("Device not found: {0}")]
+    DeviceNotFound(String),
+
+    /// The config XML could not be parsed
+    #[error("Failed to parse config: {0}")]
+    ConfigParse(String),
+
+    /// The config could not be written out
+    #[error("Failed to save config: {0}")]
+    ConfigSave(String),
+
+    /// No config has been loaded yet
+    #[error("No config loaded")]
+    NoConfig,
+
+    /// The loaded config has no mapping with the requested guid
+    #[error("Mapping not found: {0}")]
+    MappingNotFound(String),
+
+    /// No profile with this name was found by `load_profiles`
+    #[error("Profile not found: {0}")]
+    ProfileNotFound(String),
+
+    /// The profile directory could not be listed
+    #[error("Failed to read profile directory: {0}")]
+    ProfileDir(String),
+
+    /// The display type can't be driven, e.g. a typo in the config
+    #[error("Unsupported display type: {0}")]
+    UnsupportedDisplay(String),
+
+    /// A mapping with this guid already exists
+    #[error("Duplicate mapping: {0}")]
+    DuplicateMapping(String),
+
+    /// The target (device or sim) is not connected
+    #[error("Not connected")]
+    NotConnected,
+
+    /// Reading or writing the serial port failed (including port busy)
+    #[error("Serial I/O error: {0}")]
+    SerialIo(#[from] std::io::Error),
+
+    /// Serial ports could not be listed; the message says what to do about it
+    #[error("{0}")]
+    PortEnumeration(String),
+
+    /// The device answered with something we couldn't understand
+    #[error("Unexpected device response: {0}")]
+    Protocol(String),
+
+    /// avrdude is missing or failed
+    #[error("Flash tool error: {0}")]
+    FlashTool(String),
+
+    /// Errors bubbled up from the sim client
+    #[error(transparent)]
+    Sim(#[from] anyhow::Error),
+}
+
+impl From<serialport::Error> for CoreError {
+    fn from(e: serialport::Error) -> Self {
+        CoreError::SerialIo(e.into())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, CoreError>;
@@ -1,4 +1,4 @@
-use anyhow::{anyhow, Result};
+use crate::error::{CoreError, Result};
 use std::io::{BufRead, BufReader};
 use std::process::{Command, Stdio};
 use std::sync::mpsc;
@@ -13,6 +13,24 @@ pub enum BoardType {
 }
 
 impl BoardType {
+    /// Map an AVR device signature to the board that carries that MCU
+    pub fn from_signature(signature: [u8; 3]) -> Option<Self> {
+        match signature {
+            [0x1E, 0x98, 0x01] => Some(BoardType::ArduinoMega),
+            [0x1E, 0x95, 0x87] => Some(BoardType::ArduinoProMicro),
+            [0x1E, 0x95, 0x0F] => Some(BoardType::ArduinoNano),
+            _ => None,
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        match self {
+            BoardType::ArduinoMega => "Arduino Mega",
+            BoardType::ArduinoProMicro => "Arduino Pro Micro",
+            BoardType::ArduinoNano => "Arduino Nano",
+        }
+    }
+
     pub fn avrdude_part(&self) -> &str {
         match self {
             BoardType::ArduinoMega => "atmega2560",
@@ -75,7 +93,12 @@ pub fn flash_firmware(
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
-        .map_err(|e| anyhow!("Failed to start avrdude: {}. Is avrdude installed?", e))?;
+        .map_err(|e| {
+            CoreError::FlashTool(format!(
+                "Failed to start avrdude: {}. Is avrdude installed?",
+                e
+            ))
+        })?;
 
     // Parse stderr for progress (avrdude outputs progress there)
     if let Some(stderr) = child.stderr.take() {
@@ -83,7 +106,7 @@ pub fn flash_firmware(
         let tx = progress_tx.clone();
 
         thread::spawn(move || {
-            for line in reader.lines().map_while(Result::ok) {
+            for line in reader.lines().map_while(std::result::Result::ok) {
                 // Parse progress from avrdude output
                 // Lines like "Writing | ################################################## | 100%"
                 if line.contains('%') {
@@ -111,8 +134,96 @@ pub fn flash_firmware(
         }
         Ok(())
     } else {
-        Err(anyhow!("avrdude exited with status: {}", status))
+        Err(CoreError::FlashTool(format!(
+            "avrdude exited with status: {}",
+            status
+        )))
+    }
+}
+
+/// Detect which supported board is on a port by reading its MCU signature
+pub fn detect_board(port: &str) -> Result<BoardType> {
+    // Each board needs its own programmer and baud rate, so try them in turn
+    for candidate in [
+        BoardType::ArduinoMega,
+        BoardType::ArduinoProMicro,
+        BoardType::ArduinoNano,
+    ] {
+        match read_signature(port, &candidate) {
+            Ok(signature) => {
+                return BoardType::from_signature(signature).ok_or_else(|| {
+                    CoreError::FlashTool(format!(
+                        "Unknown device signature 0x{:02X}{:02X}{:02X}",
+                        signature[0], signature[1], signature[2]
+                    ))
+                });
+            }
+            Err(e) => log::debug!("No signature as {:?}: {}", candidate, e),
+        }
+    }
+    Err(CoreError::FlashTool(format!(
+        "Could not read a device signature on {}",
+        port
+    )))
+}
+
+fn read_signature(port: &str, board: &BoardType) -> Result<[u8; 3]> {
+    let output = Command::new("avrdude")
+        .args([
+            "-p",
+            board.avrdude_part(),
+            "-c",
+            board.avrdude_programmer(),
+            "-P",
+            port,
+            "-b",
+            &board.baud_rate().to_string(),
+            // Read the signature even if it doesn't match the part we guessed
+            "-F",
+            "-U",
+            "signature:r:-:h",
+        ])
+        .output()
+        .map_err(|e| {
+            CoreError::FlashTool(format!(
+                "Failed to start avrdude: {}. Is avrdude installed?",
+                e
+            ))
+        })?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    parse_signature(&stdout)
+        .or_else(|| parse_signature(&stderr))
+        .ok_or_else(|| CoreError::FlashTool("avrdude did not report a signature".to_string()))
+}
+
+/// Parse the signature bytes from avrdude output
+pub fn parse_signature(output: &str) -> Option<[u8; 3]> {
+    for line in output.lines() {
+        let line = line.trim();
+
+        let bytes: Vec<u8> = line
+            .split(',')
+            .filter_map(|b| u8::from_str_radix(b.trim().trim_start_matches("0x"), 16).ok())
+            .collect();
+        if line.starts_with("0x") && bytes.len() == 3 {
+            return Some([bytes[0], bytes[1], bytes[2]]);
+        }
+
+        if let Some(pos) = line.find("Device signature = 0x") {
+            let hex: String = line[pos + "Device signature = 0x".len()..]
+                .chars()
+                .take_while(|c| c.is_ascii_hexdigit())
+                .collect();
+            if let Ok(value) = u32::from_str_radix(&hex, 16) {
+                if hex.len() == 6 {
+                    return Some([(value >> 16) as u8, (value >> 8) as u8, value as u8]);
+                }
+            }
+        }
     }
+    None
 }
 
 /// Check if avrdude is available on the system
@@ -124,3 +235,39 @@ pub fn check_avrdude() -> bool {
         .status()
         .is_ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_board_from_signature() {
+        assert_eq!(
+            BoardType::from_signature([0x1E, 0x98, 0x01]),
+            Some(BoardType::ArduinoMega)
+        );
+        assert_eq!(
+            BoardType::from_signature([0x1E, 0x95, 0x87]),
+            Some(BoardType::ArduinoProMicro)
+        );
+        assert_eq!(
+            BoardType::from_signature([0x1E, 0x95, 0x0F]),
+            Some(BoardType::ArduinoNano)
+        );
+        // ATmega168
+        assert_eq!(BoardType::from_signature([0x1E, 0x94, 0x06]), None);
+    }
+
+    #[test]
+    fn test_parse_signature() {
+        assert_eq!(parse_signature("0x1e,0x98,0x1\n"), Some([0x1E, 0x98, 0x01]));
+        assert_eq!(
+            parse_signature("avrdude: Device signature = 0x1e950f (probably m328p)"),
+            Some([0x1E, 0x95, 0x0F])
+        );
+        assert_eq!(
+            parse_signature("avrdude: stk500_recv(): programmer is not responding"),
+            None
+        );
+    }
+}
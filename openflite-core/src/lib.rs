@@ -1,100 +1,574 @@
 pub mod config;
 pub mod device;
+pub mod error;
 pub mod flash;
 pub mod mapping;
+#[cfg(test)]
+mod mock;
 pub mod protocol;
+pub mod sink;
+#[cfg(test)]
+mod synthetic;
+mod variables;
 
 #[derive(Debug, Clone)]
 pub enum Event {
+    /// A board was added; carries its serial
     DeviceDetected(String),
-    SimConnected(String),
+    /// A board stopped responding; carries its serial
+    DeviceDisconnected(String),
+    /// A sim client connected; `demo` is set for the built-in demo backend
+    SimConnected {
+        name: String,
+        demo: bool,
+    },
+    /// Connecting a sim client failed; carries the error
+    SimConnectFailed(String),
     SimDisconnected,
-    VariableChanged { name: String, value: f64 },
+    VariableChanged {
+        name: String,
+        value: f64,
+    },
     CommandSent(String),
+    /// Outcome of probing one serial port during `scan_devices`
+    PortProbed {
+        port: String,
+        result: ProbeResult,
+    },
+    /// Something went wrong outside any one request, e.g. the run loop stalled
+    Error(String),
 }
 
+/// What answered on a probed serial port
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProbeResult {
+    /// A MobiFlight board with this serial
+    Detected(String),
+    /// The port couldn't be opened or answered with something else
+    Rejected(String),
+    /// Nothing answered GetInfo in time
+    TimedOut,
+}
+
+pub use crate::error::CoreError;
+
 use crate::device::MobiFlightDevice;
 use crate::mapping::MappingEngine;
-use crate::protocol::Response;
-use openflite_connect::SimClient;
+use crate::protocol::{Command, Response};
+use crate::sink::{OutputSink, SerialSink};
+use crate::variables::VariableTracker;
+use openflite_connect::{SimClient, VariableMeta};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
+const HEARTBEAT_REPLY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Runtime settings for `Core`
+#[derive(Debug, Clone)]
+pub struct CoreConfig {
+    /// Ping devices that have been idle this long; `None` disables the heartbeat
+    pub heartbeat_interval: Option<Duration>,
+    /// Send Init and switch every configured output off when a device is added
+    pub init_on_connect: bool,
+    /// Light every output this long during init; the adding call blocks meanwhile
+    pub lamp_test: Option<Duration>,
+    /// Report an error when `run` stalls this long; `None` disables the watchdog
+    pub watchdog_timeout: Option<Duration>,
+    /// Also switch every configured output off when the watchdog fires
+    pub watchdog_safe_outputs: bool,
+}
+
+impl Default for CoreConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval: Some(Duration::from_secs(5)),
+            init_on_connect: true,
+            lamp_test: None,
+            watchdog_timeout: Some(Duration::from_secs(2)),
+            watchdog_safe_outputs: false,
+        }
+    }
+}
+
 pub struct Core {
+    config: CoreConfig,
     event_tx: mpsc::UnboundedSender<Event>,
+    /// Every write to a board happens under this lock, so frames never interleave
     devices: Arc<Mutex<Vec<MobiFlightDevice>>>,
     sim_client: Arc<Mutex<Option<Box<dyn SimClient + Send>>>>,
     mapping_engine: Arc<Mutex<Option<MappingEngine>>>,
     injected_responses: Arc<Mutex<Vec<(String, Response)>>>,
+    profiles: Arc<Mutex<BTreeMap<String, PathBuf>>>,
+    active_profile: Arc<Mutex<Option<String>>>,
+    variables: Arc<Mutex<VariableTracker>>,
+    watchdog: Arc<Mutex<Watchdog>>,
+    output_sink: Arc<Mutex<Arc<dyn OutputSink>>>,
+}
+
+#[derive(Default)]
+struct Watchdog {
+    last_iteration: Option<Instant>,
+    tripped: bool,
 }
 
 impl Core {
     pub fn new() -> (Self, mpsc::UnboundedReceiver<Event>) {
+        Self::with_config(CoreConfig::default())
+    }
+
+    pub fn with_config(config: CoreConfig) -> (Self, mpsc::UnboundedReceiver<Event>) {
         let (tx, rx) = mpsc::unbounded_channel();
+        let devices = Arc::new(Mutex::new(Vec::new()));
+        let output_sink: Arc<dyn OutputSink> = Arc::new(SerialSink::new(devices.clone()));
         (
             Self {
+                config,
                 event_tx: tx,
-                devices: Arc::new(Mutex::new(Vec::new())),
+                devices,
                 sim_client: Arc::new(Mutex::new(None)),
                 mapping_engine: Arc::new(Mutex::new(None)),
                 injected_responses: Arc::new(Mutex::new(Vec::new())),
+                profiles: Arc::new(Mutex::new(BTreeMap::new())),
+                active_profile: Arc::new(Mutex::new(None)),
+                variables: Arc::new(Mutex::new(VariableTracker::default())),
+                watchdog: Arc::new(Mutex::new(Watchdog::default())),
+                output_sink: Arc::new(Mutex::new(output_sink)),
             },
             rx,
         )
     }
 
-    pub fn load_config(&self, xml_content: &str) -> Result<(), anyhow::Error> {
+    pub fn load_config(&self, xml_content: &str) -> Result<(), CoreError> {
         let project = crate::config::MobiFlightProject::load(xml_content)?;
-        let mut engine = self.mapping_engine.lock().unwrap();
-        *engine = Some(MappingEngine::new(project));
+        {
+            let mut engine = self.mapping_engine.lock().unwrap();
+            *engine = Some(MappingEngine::new(project));
+        }
+        *self.active_profile.lock().unwrap() = None;
+        self.warn_unsupported_capabilities();
+        self.define_bar_glyphs(None);
         Ok(())
     }
 
-    pub fn set_sim_client(
-        &self,
-        mut client: Box<dyn SimClient + Send>,
-    ) -> Result<(), anyhow::Error> {
-        client.connect()?;
-        let mut sim = self.sim_client.lock().unwrap();
-        *sim = Some(client);
+    /// Write the loaded config, including later edits, to `path`
+    pub fn save_config(&self, path: impl AsRef<Path>) -> Result<(), CoreError> {
+        let xml = {
+            let mapping = self.mapping_engine.lock().unwrap();
+            let engine = mapping.as_ref().ok_or(CoreError::NoConfig)?;
+            engine.project().to_xml()?
+        };
+        std::fs::write(path.as_ref(), xml)
+            .map_err(|e| CoreError::ConfigSave(format!("{}: {}", path.as_ref().display(), e)))
+    }
+
+    fn define_bar_glyphs(&self, serial: Option<&str>) {
+        let serials: Vec<String> = {
+            let devices = self.devices.lock().unwrap();
+            devices
+                .iter()
+                .filter(|d| d.connected && serial.is_none_or(|s| d.serial == s))
+                .map(|d| d.serial.clone())
+                .collect()
+        };
+        let sink = self.output_sink();
+        for serial in serials {
+            let needed = self
+                .mapping_engine
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_some_and(|engine| engine.uses_bar_glyphs(&serial));
+            if needed {
+                if let Err(e) = sink.send(&serial, &crate::mapping::bar_glyph_actions(&serial)) {
+                    log::warn!("Failed to define bar glyphs on {}: {}", serial, e);
+                }
+            }
+        }
+    }
+
+    /// Use every `*.xml` file in `dir` as a profile; returns the names, sorted
+    pub fn load_profiles(&self, dir: impl AsRef<Path>) -> Result<Vec<String>, CoreError> {
+        let dir = dir.as_ref();
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| CoreError::ProfileDir(format!("{}: {}", dir.display(), e)))?;
+
+        let mut profiles = BTreeMap::new();
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            let is_xml = path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("xml"));
+            if !is_xml || !path.is_file() {
+                continue;
+            }
+            if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                profiles.insert(name.to_string(), path.clone());
+            }
+        }
+
+        let names = profiles.keys().cloned().collect();
+        *self.profiles.lock().unwrap() = profiles;
+        Ok(names)
+    }
+
+    /// Names of the profiles found by the last `load_profiles`
+    pub fn profiles(&self) -> Vec<String> {
+        self.profiles.lock().unwrap().keys().cloned().collect()
+    }
+
+    pub fn active_profile(&self) -> Option<String> {
+        self.active_profile.lock().unwrap().clone()
+    }
+
+    /// Switch to another profile, switching off outputs only the old one drove
+    pub fn activate_profile(&self, name: &str) -> Result<(), CoreError> {
+        let path = self
+            .profiles
+            .lock()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| CoreError::ProfileNotFound(name.to_string()))?;
+        let xml = std::fs::read_to_string(&path)
+            .map_err(|e| CoreError::ConfigParse(format!("{}: {}", path.display(), e)))?;
+        let engine = MappingEngine::new(crate::config::MobiFlightProject::load(&xml)?);
+
+        let orphaned = {
+            let mut mapping = self.mapping_engine.lock().unwrap();
+            let still_driven = engine.safe_state_actions();
+            let orphaned: Vec<_> = match mapping.as_ref() {
+                Some(old) => old
+                    .safe_state_actions()
+                    .into_iter()
+                    .filter(|action| !still_driven.contains(action))
+                    .collect(),
+                None => Vec::new(),
+            };
+            *mapping = Some(engine);
+            orphaned
+        };
+
+        *self.active_profile.lock().unwrap() = Some(name.to_string());
+        log::info!(
+            "Activated profile {} ({} outputs switched off)",
+            name,
+            orphaned.len()
+        );
+        self.apply_hardware_outputs(orphaned);
+        self.warn_unsupported_capabilities();
+        self.define_bar_glyphs(None);
+        Ok(())
+    }
+
+    /// Replace the sim client, restoring the old one if the new one won't connect
+    pub fn set_sim_client(&self, mut client: Box<dyn SimClient + Send>) -> Result<(), CoreError> {
+        let mut previous = self.sim_client.lock().unwrap().take();
+        if let Some(old) = previous.as_mut() {
+            if let Err(e) = old.disconnect() {
+                log::warn!("Failed to disconnect previous sim client: {}", e);
+            }
+            self.variables.lock().unwrap().clear();
+        }
+
+        // Connect without holding the lock so a slow connect can't stall `run`
+        if let Err(e) = client.connect() {
+            self.broadcast(Event::SimConnectFailed(e.to_string()));
+            if let Some(mut old) = previous {
+                match old.connect() {
+                    Ok(()) => *self.sim_client.lock().unwrap() = Some(old),
+                    Err(re) => {
+                        log::warn!("Failed to restore previous sim client: {}", re);
+                        self.broadcast(Event::SimDisconnected);
+                    }
+                }
+            }
+            return Err(e.into());
+        }
+
+        let (name, demo) = (client.name().to_string(), client.is_demo());
+        *self.sim_client.lock().unwrap() = Some(client);
+        if previous.is_some() {
+            self.broadcast(Event::SimDisconnected);
+        }
+        self.broadcast(Event::SimConnected { name, demo });
+        self.warn_unsupported_capabilities();
         Ok(())
     }
 
+    /// The sim client's current error, e.g. an unreadable bridge response
+    pub fn sim_last_error(&self) -> Option<String> {
+        let sim = self.sim_client.lock().unwrap();
+        sim.as_ref().and_then(|client| client.last_error())
+    }
+
+    /// Variables the connected sim says it can provide, e.g. for autocomplete
+    pub fn available_variables(&self) -> Vec<VariableMeta> {
+        let sim = self.sim_client.lock().unwrap();
+        sim.as_ref()
+            .map(|client| client.available_variables())
+            .unwrap_or_default()
+    }
+
+    /// Variables the loaded config reads that the connected sim doesn't list
+    pub fn unknown_variables(&self) -> Vec<String> {
+        let sim = self.sim_client.lock().unwrap();
+        let mapping = self.mapping_engine.lock().unwrap();
+
+        let (Some(client), Some(engine)) = (sim.as_ref(), mapping.as_ref()) else {
+            return Vec::new();
+        };
+        let available = client.available_variables();
+        if available.is_empty() {
+            return Vec::new();
+        }
+        engine
+            .source_variables()
+            .into_iter()
+            .filter(|name| !available.iter().any(|v| &v.name == name))
+            .collect()
+    }
+
+    /// Features the loaded config uses that the connected sim can't provide
+    pub fn unsupported_capabilities(&self) -> Vec<String> {
+        let sim = self.sim_client.lock().unwrap();
+        let mapping = self.mapping_engine.lock().unwrap();
+
+        let mut unsupported = Vec::new();
+        if let (Some(client), Some(engine)) = (sim.as_ref(), mapping.as_ref()) {
+            let available = client.capabilities();
+            let required = engine.required_capabilities();
+
+            if required.write_variables && !available.write_variables {
+                unsupported.push("variable writes".to_string());
+            }
+            if required.commands && !available.commands {
+                unsupported.push("commands".to_string());
+            }
+            if required.subscriptions && !available.subscriptions {
+                unsupported.push("subscriptions".to_string());
+            }
+            if required.string_variables && !available.string_variables {
+                unsupported.push("string variables".to_string());
+            }
+        }
+        unsupported
+    }
+
+    fn warn_unsupported_capabilities(&self) {
+        for capability in self.unsupported_capabilities() {
+            log::warn!(
+                "Loaded config uses {} but the sim backend doesn't support them; mappings that need them won't work",
+                capability
+            );
+        }
+    }
+
     pub fn disconnect_sim(&self) {
         let mut sim = self.sim_client.lock().unwrap();
         if let Some(mut client) = sim.take() {
             let _ = client.disconnect();
+            self.variables.lock().unwrap().clear();
+            self.broadcast(Event::SimDisconnected);
         }
-        self.broadcast(Event::SimDisconnected);
     }
 
-    pub fn scan_devices(&self) -> Result<(), anyhow::Error> {
+    /// Probe every serial port not in use and add the MobiFlight boards found
+    pub fn scan_devices(&self) -> Result<(), CoreError> {
         let ports = MobiFlightDevice::scan()?;
+        let ports: Vec<String> = {
+            let devices = self.devices.lock().unwrap();
+            ports
+                .into_iter()
+                .filter(|port| {
+                    !devices
+                        .iter()
+                        .any(|d| d.connected && d.port_name.as_ref() == Some(port))
+                })
+                .collect()
+        };
+        self.probe_ports(ports, MobiFlightDevice::new);
+        Ok(())
+    }
+
+    fn probe_ports<F>(&self, ports: Vec<String>, probe: F)
+    where
+        F: Fn(&str) -> Result<MobiFlightDevice, CoreError> + Sync,
+    {
+        let probe = &probe;
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = ports
+                .iter()
+                .map(|port| scope.spawn(move || probe(port)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| {
+                    h.join()
+                        .unwrap_or_else(|_| Err(CoreError::Protocol("probe panicked".to_string())))
+                })
+                .collect()
+        });
+
+        let mut added = Vec::new();
         let mut devices = self.devices.lock().unwrap();
+        for (port, outcome) in ports.into_iter().zip(results) {
+            let result = match outcome {
+                Ok(dev) => {
+                    let serial = dev.serial.clone();
+                    added.push((serial.clone(), dev.name.clone()));
+                    Self::register_device(&mut devices, dev);
+                    ProbeResult::Detected(serial)
+                }
+                Err(CoreError::SerialIo(e)) if e.kind() == std::io::ErrorKind::TimedOut => {
+                    ProbeResult::TimedOut
+                }
+                Err(e) => ProbeResult::Rejected(e.to_string()),
+            };
+            log::debug!("Probed {}: {:?}", port, result);
+            self.broadcast(Event::PortProbed { port, result });
+        }
+        drop(devices);
+
+        for (serial, name) in added {
+            self.device_added(&serial, name);
+        }
+    }
+
+    /// Add a device opened by the caller (e.g. over a custom transport)
+    pub fn add_device(&self, dev: MobiFlightDevice) {
+        let (serial, name) = (dev.serial.clone(), dev.name.clone());
+        Self::register_device(&mut self.devices.lock().unwrap(), dev);
+        self.device_added(&serial, name);
+    }
+
+    fn register_device(devices: &mut Vec<MobiFlightDevice>, dev: MobiFlightDevice) {
+        devices.retain(|d| {
+            d.connected
+                || (d.serial != dev.serial
+                    && (d.port_name.is_none() || d.port_name != dev.port_name))
+        });
+        devices.push(dev);
+    }
 
-        for port in ports {
-            if !devices.iter().any(|d| d.serial == port) {
-                // Using serial as proxy for now
-                if let Ok(dev) = MobiFlightDevice::new(&port) {
-                    let name = dev.name.clone();
-                    devices.push(dev);
-                    self.broadcast(Event::DeviceDetected(name));
+    fn device_added(&self, serial: &str, name: String) {
+        if self.config.init_on_connect {
+            if let Err(e) = self.initialize_device(serial) {
+                log::warn!("Failed to initialize {}: {}", name, e);
+            }
+        }
+        self.broadcast(Event::DeviceDetected(serial.to_string()));
+    }
+
+    fn initialize_device(&self, serial: &str) -> Result<(), CoreError> {
+        use crate::mapping::HardwareAction;
+
+        let (lamp_test, off, bar_glyphs) = {
+            let mut mapping = self.mapping_engine.lock().unwrap();
+            match mapping.as_mut() {
+                Some(engine) => {
+                    // Everything is off after init; send current values again
+                    engine.forget_sent_outputs(Some(serial));
+                    (
+                        engine.lamp_test_actions(),
+                        engine.safe_state_actions(),
+                        engine.uses_bar_glyphs(serial),
+                    )
                 }
+                None => (Vec::new(), Vec::new(), false),
             }
+        };
+        let for_device = |actions: Vec<HardwareAction>| {
+            actions
+                .into_iter()
+                .filter(|a| a.serial() == serial)
+                .collect::<Vec<_>>()
+        };
+
+        let sink = self.output_sink();
+        let init = HardwareAction::Init {
+            serial: serial.to_string(),
+        };
+        sink.send(serial, &[init])?;
+        if let Some(duration) = self.config.lamp_test {
+            sink.send(serial, &for_device(lamp_test))?;
+            std::thread::sleep(duration);
         }
-        Ok(())
+        let mut off = for_device(off);
+        if bar_glyphs {
+            off.extend(crate::mapping::bar_glyph_actions(serial));
+        }
+        sink.send(serial, &off)
     }
 
-    pub async fn run(&self) -> Result<(), anyhow::Error> {
+    pub async fn run(&self) -> Result<(), CoreError> {
         loop {
-            let hardware_responses = self.collect_hardware_events();
-            let hardware_actions = self.process_simulation_sync(hardware_responses);
-            self.apply_hardware_outputs(hardware_actions);
-
+            self.run_iteration();
             tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
         }
     }
 
+    fn run_iteration(&self) {
+        self.mark_iteration();
+        let hardware_responses = self.collect_hardware_events();
+        let hardware_actions = self.process_simulation_sync(hardware_responses);
+        self.apply_hardware_outputs(hardware_actions);
+        self.send_heartbeats();
+    }
+
+    fn mark_iteration(&self) {
+        let mut watchdog = self.watchdog.lock().unwrap();
+        watchdog.last_iteration = Some(Instant::now());
+        if std::mem::take(&mut watchdog.tripped) {
+            log::info!("Run loop recovered");
+        }
+    }
+
+    /// Report (and optionally safe) a `run` stall; fires once per stall
+    pub fn check_watchdog(&self) -> bool {
+        let Some(timeout) = self.config.watchdog_timeout else {
+            return false;
+        };
+        let stalled_for = {
+            let mut watchdog = self.watchdog.lock().unwrap();
+            let Some(last) = watchdog.last_iteration else {
+                return false;
+            };
+            if watchdog.tripped || last.elapsed() < timeout {
+                return false;
+            }
+            watchdog.tripped = true;
+            last.elapsed()
+        };
+
+        let msg = format!(
+            "Run loop stalled for {:.1}s; outputs are not being updated",
+            stalled_for.as_secs_f64()
+        );
+        log::error!("{}", msg);
+        self.broadcast(Event::Error(msg));
+        if self.config.watchdog_safe_outputs {
+            self.all_outputs_off();
+        }
+        true
+    }
+
+    /// Call `check_watchdog` periodically on a thread of its own
+    pub fn spawn_watchdog(self: &Arc<Self>) -> Option<std::thread::JoinHandle<()>> {
+        let timeout = self.config.watchdog_timeout?;
+        let core = Arc::downgrade(self);
+        Some(std::thread::spawn(move || loop {
+            std::thread::sleep(timeout / 4);
+            match core.upgrade() {
+                Some(core) => {
+                    core.check_watchdog();
+                }
+                None => break,
+            }
+        }))
+    }
+
     fn collect_hardware_events(&self) -> Vec<(String, Response)> {
         let mut hardware_responses = Vec::new();
         // 1. Process injected responses first
@@ -108,12 +582,42 @@ impl Core {
         for dev in devices.iter_mut() {
             let resps = dev.poll_events();
             for resp in resps {
-                hardware_responses.push((dev.name.clone(), resp));
+                hardware_responses.push((dev.serial.clone(), resp));
             }
         }
         hardware_responses
     }
 
+    fn send_heartbeats(&self) {
+        let Some(interval) = self.config.heartbeat_interval else {
+            return;
+        };
+        let reply_timeout = interval.max(HEARTBEAT_REPLY_TIMEOUT);
+
+        let mut devices = self.devices.lock().unwrap();
+        for dev in devices.iter_mut().filter(|d| d.connected) {
+            let result = if dev.unanswered_for().is_some_and(|t| t >= reply_timeout) {
+                Err(CoreError::Protocol("no reply to heartbeat".to_string()))
+            } else if dev.idle_time() >= interval {
+                dev.ping()
+            } else {
+                Ok(())
+            };
+            if let Err(e) = result {
+                log::warn!("Heartbeat to {} failed: {}", dev.name, e);
+                dev.connected = false;
+                self.device_lost(&dev.serial);
+            }
+        }
+    }
+
+    fn device_lost(&self, serial: &str) {
+        if let Some(engine) = self.mapping_engine.lock().unwrap().as_ref() {
+            engine.release_inputs(serial);
+        }
+        self.broadcast(Event::DeviceDisconnected(serial.to_string()));
+    }
+
     fn process_simulation_sync(
         &self,
         hardware_responses: Vec<(String, Response)>,
@@ -121,114 +625,344 @@ impl Core {
         let mut hardware_actions = Vec::new();
         let mut sim = self.sim_client.lock().unwrap();
 
-        if let Some(client) = sim.as_mut() {
-            let _ = client.poll();
+        // Outputs keep running without a sim so System sources still work
+        let mut data = match sim.as_mut() {
+            Some(client) => {
+                let _ = client.poll();
+                client.get_all_variables()
+            }
+            None => std::collections::HashMap::new(),
+        };
+        self.variables.lock().unwrap().record(&data);
 
-            let mapping = self.mapping_engine.lock().unwrap();
-            if let Some(engine) = mapping.as_ref() {
-                // A. Sim -> Hardware
-                let data = client.get_all_variables();
-                hardware_actions = engine.process_outputs(&data);
-
-                // B. Hardware -> Sim
-                for (dev_name, resp) in hardware_responses {
-                    // Update UI cache for inputs too
-                    if let Response::InputEvent {
-                        name: pin_name,
-                        value,
-                    } = &resp
-                    {
-                        self.broadcast(Event::VariableChanged {
-                            name: format!("{}:{}", dev_name, pin_name),
-                            value: value.parse().unwrap_or(0.0),
-                        });
-                    }
+        let mut mapping = self.mapping_engine.lock().unwrap();
+        if let Some(engine) = mapping.as_mut() {
+            // A. Sim -> Hardware
+            data.extend(engine.system_variables());
+            hardware_actions = engine.process_changed_outputs(&data);
 
-                    let sim_actions = engine.process_inputs(&resp);
-                    for action in sim_actions {
-                        match action {
-                            crate::mapping::SimAction::Command(cmd) => {
-                                let _ = client.execute_command(&cmd);
-                            }
-                            crate::mapping::SimAction::WriteDataref(dref, val) => {
-                                let _ = client.write_variable(&dref, val);
-                            }
-                            _ => {}
-                        }
-                    }
+            // B. Hardware -> Sim
+            for (serial, resp) in hardware_responses {
+                // Update UI cache for inputs too
+                if let Response::InputEvent {
+                    name: pin_name,
+                    value,
+                } = &resp
+                {
+                    self.broadcast(Event::VariableChanged {
+                        name: format!("{}:{}", serial, pin_name),
+                        value: value.parse().unwrap_or(0.0),
+                    });
+                }
+
+                let Some(client) = sim.as_mut() else {
+                    continue;
+                };
+                for action in engine.process_inputs(&serial, &resp, &data) {
+                    Self::execute_sim_action(client.as_mut(), action, &mut data);
+                }
+            }
+
+            // Held buttons that repeat
+            if let Some(client) = sim.as_mut() {
+                for action in engine.repeat_actions(&data) {
+                    Self::execute_sim_action(client.as_mut(), action, &mut data);
                 }
             }
         }
         hardware_actions
     }
 
+    fn execute_sim_action(
+        client: &mut (dyn SimClient + Send),
+        action: crate::mapping::SimAction,
+        data: &mut std::collections::HashMap<String, f64>,
+    ) {
+        match action {
+            crate::mapping::SimAction::Command(cmd) => {
+                let _ = client.execute_command(&cmd);
+            }
+            crate::mapping::SimAction::WriteDataref(dref, val) => {
+                let _ = client.write_variable(&dref, val);
+                data.insert(dref, val);
+            }
+            crate::mapping::SimAction::None => {}
+        }
+    }
+
+    /// Send engine output actions somewhere other than the connected boards
+    pub fn set_output_sink(&self, sink: Box<dyn OutputSink>) {
+        *self.output_sink.lock().unwrap() = Arc::from(sink);
+    }
+
+    fn output_sink(&self) -> Arc<dyn OutputSink> {
+        self.output_sink.lock().unwrap().clone()
+    }
+
     fn apply_hardware_outputs(&self, hardware_actions: Vec<crate::mapping::HardwareAction>) {
-        if !hardware_actions.is_empty() {
-            let mut devices = self.devices.lock().unwrap();
-            for action in hardware_actions {
-                match action {
-                    crate::mapping::HardwareAction::SetPin { serial, pin, value } => {
-                        if let Some(dev) = devices.iter_mut().find(|d| d.serial == serial) {
-                            let _ = dev.set_pin(pin, value);
-                        }
-                    }
-                    crate::mapping::HardwareAction::Set7Segment {
-                        serial,
-                        module,
-                        index,
-                        value,
-                    } => {
-                        if let Some(dev) = devices.iter_mut().find(|d| d.serial == serial) {
-                            let _ = dev.set_7segment(module, index, &value);
-                        }
-                    }
-                    crate::mapping::HardwareAction::SetLCD {
-                        serial,
-                        display_id,
-                        line,
-                        text,
-                    } => {
-                        if let Some(dev) = devices.iter_mut().find(|d| d.serial == serial) {
-                            let _ = dev.set_lcd(display_id, line, &text);
-                        }
-                    }
-                    crate::mapping::HardwareAction::SetStepper {
-                        serial,
-                        motor_id,
-                        steps,
-                    } => {
-                        if let Some(dev) = devices.iter_mut().find(|d| d.serial == serial) {
-                            let _ = dev.set_stepper(motor_id, steps);
-                        }
+        // Group by device, keeping first-seen device order and action order
+        let mut batches: Vec<(String, Vec<crate::mapping::HardwareAction>)> = Vec::new();
+        for action in hardware_actions {
+            match batches
+                .iter_mut()
+                .find(|(serial, _)| serial == action.serial())
+            {
+                Some((_, actions)) => actions.push(action),
+                None => batches.push((action.serial().to_string(), vec![action])),
+            }
+        }
+
+        let sink = self.output_sink();
+        for (serial, actions) in batches {
+            match sink.send(&serial, &actions) {
+                Ok(()) => {
+                    if let Some(engine) = self.mapping_engine.lock().unwrap().as_mut() {
+                        engine.mark_outputs_sent(&actions);
                     }
-                    crate::mapping::HardwareAction::SetRGB {
-                        serial,
-                        led_id,
-                        r,
-                        g,
-                        b,
-                    } => {
-                        if let Some(dev) = devices.iter_mut().find(|d| d.serial == serial) {
-                            let _ = dev.set_rgb(led_id, r, g, b);
-                        }
+                }
+                // Not marked sent, so OnChange outputs are retried next tick
+                Err(e) => log::debug!("Dropped {} outputs for {}: {}", actions.len(), serial, e),
+            }
+        }
+    }
+
+    /// Panic-stop: drive every output off and reset boards the config doesn't cover
+    pub fn all_outputs_off(&self) {
+        let serials: Vec<String> = {
+            let devices = self.devices.lock().unwrap();
+            devices
+                .iter()
+                .filter(|d| d.connected)
+                .map(|d| d.serial.clone())
+                .collect()
+        };
+        let mut actions = {
+            let mut mapping = self.mapping_engine.lock().unwrap();
+            match mapping.as_mut() {
+                Some(engine) => {
+                    engine.forget_sent_outputs(None);
+                    engine.safe_state_actions()
+                }
+                None => Vec::new(),
+            }
+        };
+        let unmapped: Vec<_> = serials
+            .into_iter()
+            .filter(|serial| !actions.iter().any(|a| a.serial() == serial))
+            .map(|serial| crate::mapping::HardwareAction::Init { serial })
+            .collect();
+        actions.extend(unmapped);
+        log::warn!("All outputs off: sending {} off commands", actions.len());
+        self.apply_hardware_outputs(actions);
+    }
+
+    /// Drive one output directly, bypassing the sim, e.g. to check wiring
+    pub fn fire_output(
+        &self,
+        display: &crate::config::Display,
+        value: f64,
+    ) -> Result<(), CoreError> {
+        let action = MappingEngine::display_action(display, "TEST", value)
+            .ok_or_else(|| CoreError::UnsupportedDisplay(display.display_type.clone()))?;
+        self.output_sink().send(&display.serial, &[action])?;
+        self.forget_sent_outputs(&display.serial);
+        Ok(())
+    }
+
+    /// Move a servo to a raw pulse width, e.g. while calibrating a gauge
+    pub fn set_servo_raw(&self, serial: &str, servo_id: u8, pwm: u16) -> Result<(), CoreError> {
+        let action = crate::mapping::HardwareAction::SetServo {
+            serial: serial.to_string(),
+            servo_id,
+            pwm,
+        };
+        self.output_sink().send(serial, &[action])?;
+        self.forget_sent_outputs(serial);
+        Ok(())
+    }
+
+    /// Move a stepper to a raw step position, e.g. while calibrating a gauge
+    pub fn set_stepper_raw(&self, serial: &str, motor_id: u8, steps: i32) -> Result<(), CoreError> {
+        let action = crate::mapping::HardwareAction::SetStepper {
+            serial: serial.to_string(),
+            motor_id,
+            steps,
+        };
+        self.output_sink().send(serial, &[action])?;
+        self.forget_sent_outputs(serial);
+        Ok(())
+    }
+
+    fn forget_sent_outputs(&self, serial: &str) {
+        if let Some(engine) = self.mapping_engine.lock().unwrap().as_mut() {
+            engine.forget_sent_outputs(Some(serial));
+        }
+    }
+
+    /// Add one output to the loaded config without disturbing the others
+    pub fn add_output_mapping(&self, config: crate::config::OutputConfig) -> Result<(), CoreError> {
+        let guid = config.guid.clone();
+        let bar_serial = Self::bar_serial(&config);
+        {
+            let mut mapping = self.mapping_engine.lock().unwrap();
+            let engine = mapping.as_mut().ok_or(CoreError::NoConfig)?;
+            if !engine.add_output(config) {
+                return Err(CoreError::DuplicateMapping(guid));
+            }
+        }
+        if let Some(serial) = bar_serial {
+            self.define_bar_glyphs(Some(&serial));
+        }
+        Ok(())
+    }
+
+    fn bar_serial(config: &crate::config::OutputConfig) -> Option<String> {
+        let display = config.settings.display.as_ref()?;
+        (display.display_type == "LCDBar").then(|| display.serial.clone())
+    }
+
+    /// Replace the output or input with the same guid
+    pub fn update_mapping(
+        &self,
+        config: impl Into<crate::mapping::MappingConfig>,
+    ) -> Result<(), CoreError> {
+        let mut bar_serial = None;
+        let orphaned = {
+            let mut mapping = self.mapping_engine.lock().unwrap();
+            let engine = mapping.as_mut().ok_or(CoreError::NoConfig)?;
+            match config.into() {
+                crate::mapping::MappingConfig::Output(config) => {
+                    let guid = config.guid.clone();
+                    bar_serial = Self::bar_serial(&config);
+                    engine
+                        .update_output(*config)
+                        .ok_or(CoreError::MappingNotFound(guid))?
+                }
+                crate::mapping::MappingConfig::Input(config) => {
+                    let guid = config.guid.clone();
+                    if !engine.update_input(*config) {
+                        return Err(CoreError::MappingNotFound(guid));
                     }
+                    Vec::new()
                 }
             }
+        };
+        self.apply_hardware_outputs(orphaned);
+        if let Some(serial) = bar_serial {
+            self.define_bar_glyphs(Some(&serial));
+        }
+        Ok(())
+    }
+
+    /// Remove an output or input mapping; hardware only it drove is switched off
+    pub fn remove_mapping(&self, guid: &str) -> Result<(), CoreError> {
+        let orphaned = {
+            let mut mapping = self.mapping_engine.lock().unwrap();
+            let engine = mapping.as_mut().ok_or(CoreError::NoConfig)?;
+            engine
+                .remove_mapping(guid)
+                .ok_or_else(|| CoreError::MappingNotFound(guid.to_string()))?
+        };
+        self.apply_hardware_outputs(orphaned);
+        Ok(())
+    }
+
+    /// Bench testing: drive an output from `value` instead of the sim; `None` clears it
+    pub fn set_output_override(&self, guid: &str, value: Option<f64>) -> Result<(), CoreError> {
+        let actions = {
+            let mut mapping = self.mapping_engine.lock().unwrap();
+            let engine = mapping.as_mut().ok_or(CoreError::NoConfig)?;
+            engine
+                .set_override(guid, value)
+                .ok_or_else(|| CoreError::MappingNotFound(guid.to_string()))?
+        };
+        self.apply_hardware_outputs(actions);
+        Ok(())
+    }
+
+    /// Stop a `type="Warning"` output blinking
+    pub fn acknowledge_warning(&self, guid: &str) -> Result<(), CoreError> {
+        let mut mapping = self.mapping_engine.lock().unwrap();
+        let engine = mapping.as_mut().ok_or(CoreError::NoConfig)?;
+        if engine.acknowledge_warning(guid) {
+            Ok(())
+        } else {
+            Err(CoreError::MappingNotFound(guid.to_string()))
         }
     }
 
+    /// Current output overrides, by guid
+    pub fn output_overrides(&self) -> std::collections::HashMap<String, f64> {
+        let mapping = self.mapping_engine.lock().unwrap();
+        mapping
+            .as_ref()
+            .map(|engine| engine.overrides().clone())
+            .unwrap_or_default()
+    }
+
+    /// Store calibration points for a servo or stepper output
+    pub fn set_calibration(
+        &self,
+        guid: &str,
+        points: Vec<crate::config::CalibrationPoint>,
+    ) -> Result<(), CoreError> {
+        let mut mapping = self.mapping_engine.lock().unwrap();
+        let engine = mapping.as_mut().ok_or(CoreError::NoConfig)?;
+        engine.set_calibration(guid, points)
+    }
+
+    pub fn calibration(&self, guid: &str) -> Vec<crate::config::CalibrationPoint> {
+        let mapping = self.mapping_engine.lock().unwrap();
+        mapping
+            .as_ref()
+            .and_then(|engine| engine.calibration(guid))
+            .map(|points| points.to_vec())
+            .unwrap_or_default()
+    }
+
+    /// Free RAM, config checksum and uptime of one board, for diagnostics
+    pub fn read_device_status(
+        &self,
+        serial: &str,
+    ) -> Result<crate::protocol::DeviceStatus, CoreError> {
+        self.with_device(serial, |dev| dev.read_status())
+    }
+
+    /// Send a raw command to the device with the given serial
+    pub fn send_command(&self, serial: &str, cmd: Command) -> Result<(), CoreError> {
+        self.with_device(serial, |dev| dev.send_command(cmd))
+    }
+
+    fn with_device<T>(
+        &self,
+        serial: &str,
+        f: impl FnOnce(&mut MobiFlightDevice) -> Result<T, CoreError>,
+    ) -> Result<T, CoreError> {
+        let mut devices = self.devices.lock().unwrap();
+        let dev = devices
+            .iter_mut()
+            .find(|d| d.serial == serial)
+            .ok_or_else(|| CoreError::DeviceNotFound(serial.to_string()))?;
+        f(dev)
+    }
+
     pub fn broadcast(&self, event: Event) {
         let _ = self.event_tx.send(event);
     }
 
+    /// `"name (board_type)"` for each device; see `get_device_infos`
     pub fn get_devices(&self) -> Vec<String> {
-        let devices = self.devices.lock().unwrap();
-        devices
+        self.get_device_infos()
             .iter()
-            .map(|d| format!("{} ({})", d.name, d.board_type))
+            .map(|info| info.to_string())
             .collect()
     }
 
+    pub fn get_device_infos(&self) -> Vec<crate::device::DeviceInfo> {
+        let devices = self.devices.lock().unwrap();
+        devices.iter().map(|d| d.info()).collect()
+    }
+
     pub fn get_all_variables(&self) -> std::collections::HashMap<String, f64> {
         let sim = self.sim_client.lock().unwrap();
         if let Some(client) = sim.as_ref() {
@@ -238,8 +972,1242 @@ impl Core {
         }
     }
 
-    pub fn inject_hardware_response(&self, dev_name: &str, resp: Response) {
+    /// Sim variables that changed after `generation`, plus the generation to pass next
+    pub fn changed_variables_since(&self, generation: u64) -> (u64, Vec<(String, f64)>) {
+        self.variables.lock().unwrap().changed_since(generation)
+    }
+
+    /// Handle `resp` as if the board with `serial` had sent it
+    pub fn inject_hardware_response(&self, serial: &str, resp: Response) {
         let mut injected = self.injected_responses.lock().unwrap();
-        injected.push((dev_name.to_string(), resp));
+        injected.push((serial.to_string(), resp));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockSimClient, MockTransport};
+    use openflite_connect::dummy::DummyClient;
+
+    const COMMAND_CONFIG: &str = r#"
+        <MobiFlightProject>
+            <Outputs></Outputs>
+            <Inputs>
+                <Config guid="gear" active="true">
+                    <Description>GearToggle</Description>
+                    <Settings>
+                        <Button>
+                            <OnPress type="XplaneAction" cmd="sim/flight_controls/landing_gear_toggle" />
+                        </Button>
+                    </Settings>
+                </Config>
+            </Inputs>
+        </MobiFlightProject>
+    "#;
+
+    #[test]
+    fn test_unsupported_capabilities_reported() {
+        let (core, _rx) = Core::new();
+        core.load_config(COMMAND_CONFIG).unwrap();
+        assert!(core.unsupported_capabilities().is_empty());
+
+        core.set_sim_client(Box::new(DummyClient::new())).unwrap();
+        assert_eq!(
+            core.unsupported_capabilities(),
+            vec!["commands".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_unknown_variables_checked_against_catalog() {
+        let (core, _rx) = Core::new();
+        core.load_config(PIN_CONFIG).unwrap();
+        let sim = MockSimClient::default();
+        core.set_sim_client(Box::new(sim.clone())).unwrap();
+
+        // A sim that can't list its variables can't rule any out
+        assert!(core.available_variables().is_empty());
+        assert!(core.unknown_variables().is_empty());
+
+        sim.state.lock().unwrap().catalog = vec![VariableMeta {
+            name: "sim/flightmodel/position/altitude".to_string(),
+            ..Default::default()
+        }];
+        assert_eq!(core.available_variables().len(), 1);
+        assert_eq!(
+            core.unknown_variables(),
+            vec!["sim/cockpit2/controls/gear_handle_down".to_string()]
+        );
+
+        sim.state.lock().unwrap().catalog.push(VariableMeta {
+            name: "sim/cockpit2/controls/gear_handle_down".to_string(),
+            ..Default::default()
+        });
+        assert!(core.unknown_variables().is_empty());
+
+        // System sources never come from the sim
+        let mut blink = output_config("alive", "system/blink_1hz", "5");
+        blink.settings.source.as_mut().unwrap().source_type = "System".to_string();
+        core.add_output_mapping(blink).unwrap();
+        assert!(core.unknown_variables().is_empty());
+    }
+
+    #[test]
+    fn test_watchdog_fires_when_loop_stalls() {
+        let (core, mut rx) = Core::with_config(CoreConfig {
+            watchdog_timeout: Some(Duration::from_millis(50)),
+            watchdog_safe_outputs: true,
+            init_on_connect: false,
+            ..Default::default()
+        });
+        core.load_config(PIN_CONFIG).unwrap();
+        let (dev, mock) = MockTransport::device("Panel", "SN-1");
+        core.devices.lock().unwrap().push(dev);
+        let sim = MockSimClient::default();
+        core.set_sim_client(Box::new(sim.clone())).unwrap();
+
+        // Not running yet, so nothing to watch
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(!core.check_watchdog());
+
+        core.run_iteration();
+        assert!(!core.check_watchdog());
+        while rx.try_recv().is_ok() {}
+        mock.clear_written();
+
+        // The sim client wedges mid-iteration
+        sim.state.lock().unwrap().poll_delay = Some(Duration::from_millis(300));
+        let core = Arc::new(core);
+        let stalled = {
+            let core = core.clone();
+            std::thread::spawn(move || core.run_iteration())
+        };
+        std::thread::sleep(Duration::from_millis(150));
+
+        assert!(core.check_watchdog());
+        assert!(matches!(rx.try_recv(), Ok(Event::Error(msg)) if msg.contains("stalled")));
+        assert_eq!(mock.written(), "3,13,0;");
+        // Only once per stall
+        assert!(!core.check_watchdog());
+
+        stalled.join().unwrap();
+        sim.state.lock().unwrap().poll_delay = None;
+        core.run_iteration();
+        assert!(!core.watchdog.lock().unwrap().tripped);
+    }
+
+    #[test]
+    fn test_watchdog_thread_fires_on_current_thread_runtime() {
+        let (core, mut rx) = Core::with_config(CoreConfig {
+            watchdog_timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        });
+        let sim = MockSimClient::default();
+        core.set_sim_client(Box::new(sim.clone())).unwrap();
+        let core = Arc::new(core);
+        let watchdog = core.spawn_watchdog().unwrap();
+
+        // The loop wedges the only thread its runtime has
+        core.run_iteration();
+        sim.state.lock().unwrap().poll_delay = Some(Duration::from_millis(200));
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async { core.run_iteration() });
+
+        let mut events = std::iter::from_fn(|| rx.try_recv().ok());
+        assert!(events.any(|e| matches!(e, Event::Error(msg) if msg.contains("stalled"))));
+
+        drop(core);
+        watchdog.join().unwrap();
+    }
+
+    #[test]
+    fn test_output_override_without_sim() {
+        let (core, _rx) = Core::new();
+        core.load_config(PIN_CONFIG).unwrap();
+        let (dev, mock) = MockTransport::device("Panel", "SN-1");
+        core.devices.lock().unwrap().push(dev);
+
+        core.set_output_override("gear-led", Some(1.0)).unwrap();
+        assert_eq!(
+            core.output_overrides(),
+            std::collections::HashMap::from([("gear-led".to_string(), 1.0)])
+        );
+        let actions = core.process_simulation_sync(Vec::new());
+        core.apply_hardware_outputs(actions);
+        assert_eq!(mock.written(), "3,13,1;");
+
+        mock.clear_written();
+        core.set_output_override("gear-led", None).unwrap();
+        assert_eq!(mock.written(), "3,13,0;");
+        assert!(core.output_overrides().is_empty());
+
+        assert!(matches!(
+            core.set_output_override("missing", Some(1.0)),
+            Err(CoreError::MappingNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_recording_sink_sees_demo_outputs() {
+        let (core, _rx) = Core::new();
+        core.load_config(crate::config::DEMO_CONFIG).unwrap();
+        let sink = crate::sink::RecordingSink::new();
+        core.set_output_sink(Box::new(sink.clone()));
+        let sim = MockSimClient::default();
+        core.set_sim_client(Box::new(sim.clone())).unwrap();
+        let altitude_led = |value| crate::mapping::HardwareAction::SetPin {
+            serial: "DEMO-BOARD".to_string(),
+            pin: 13,
+            value,
+        };
+
+        let set_altitude = |feet: f64| {
+            sim.state
+                .lock()
+                .unwrap()
+                .variables
+                .insert("sim/flightmodel/position/altitude".to_string(), feet);
+        };
+        set_altitude(1000.0);
+        core.run_iteration();
+        set_altitude(1100.0);
+        core.run_iteration();
+        // OnChange: nothing new while the result stays the same
+        set_altitude(1200.0);
+        core.run_iteration();
+        assert_eq!(sink.actions(), vec![altitude_led(0), altitude_led(1)]);
+
+        sink.clear();
+        let display = crate::config::Display {
+            display_type: "Pin".to_string(),
+            serial: "DEMO-BOARD".to_string(),
+            pin: "13".to_string(),
+            ..Default::default()
+        };
+        core.fire_output(&display, 0.0).unwrap();
+        assert_eq!(sink.actions(), vec![altitude_led(0)]);
+    }
+
+    #[test]
+    fn test_all_outputs_off_reaches_every_device() {
+        let (core, _rx) = Core::new();
+        core.load_config(
+            r#"
+            <MobiFlightProject>
+                <Outputs>
+                    <Config guid="gear-led" active="true">
+                        <Description>Gear LED</Description>
+                        <Settings>
+                            <Source type="SimConnect" name="sim/cockpit2/controls/gear_handle_down" />
+                            <Display type="Pin" serial="SN-1" trigger="OnChange" pin="13" />
+                        </Settings>
+                    </Config>
+                    <Config guid="altitude" active="false">
+                        <Description>Altitude</Description>
+                        <Settings>
+                            <Source type="SimConnect" name="sim/flightmodel/position/altitude" />
+                            <Display type="7Segment" serial="SN-2" trigger="OnChange" pin="0" />
+                        </Settings>
+                    </Config>
+                </Outputs>
+                <Inputs></Inputs>
+            </MobiFlightProject>
+            "#,
+        )
+        .unwrap();
+
+        let (dev1, mock1) = MockTransport::device("Panel", "SN-1");
+        let (dev2, mock2) = MockTransport::device("Radio", "SN-2");
+        core.devices.lock().unwrap().extend([dev1, dev2]);
+
+        core.all_outputs_off();
+
+        assert_eq!(mock1.written(), "3,13,0;");
+        assert_eq!(mock2.written(), "15,0,0,        ;");
+    }
+
+    #[test]
+    fn test_all_outputs_off_without_config_resets_devices() {
+        let (core, _rx) = Core::new();
+        let (dev1, mock1) = MockTransport::device("Panel", "SN-1");
+        let (dev2, mock2) = MockTransport::device("Radio", "SN-2");
+        core.devices.lock().unwrap().extend([dev1, dev2]);
+
+        core.all_outputs_off();
+
+        assert_eq!(mock1.written(), "1;");
+        assert_eq!(mock2.written(), "1;");
+    }
+
+    #[test]
+    fn test_outputs_batched_per_device() {
+        let (core, _rx) = Core::new();
+        let (dev1, mock1) = MockTransport::device("Panel", "SN-1");
+        let (dev2, mock2) = MockTransport::device("Radio", "SN-2");
+        core.devices.lock().unwrap().extend([dev1, dev2]);
+
+        let digit = |serial: &str, index: u8| crate::mapping::HardwareAction::Set7Segment {
+            serial: serial.to_string(),
+            module: 0,
+            index,
+            value: index.to_string(),
+        };
+        core.apply_hardware_outputs(vec![
+            digit("SN-1", 0),
+            digit("SN-2", 0),
+            digit("SN-1", 1),
+            digit("SN-1", 2),
+        ]);
+
+        assert_eq!(mock1.flushes(), 1);
+        assert_eq!(mock1.written(), "15,0,0,0;15,0,1,1;15,0,2,2;");
+        assert_eq!(mock2.flushes(), 1);
+        assert_eq!(mock2.written(), "15,0,0,0;");
+    }
+
+    fn profile_xml(outputs: &[(&str, &str, &str)]) -> String {
+        let configs: String = outputs
+            .iter()
+            .map(|(guid, display, pin)| {
+                format!(
+                    r#"<Config guid="{guid}" active="true">
+                        <Description>{guid}</Description>
+                        <Settings>
+                            <Source type="SimConnect" name="sim/{guid}" />
+                            <Display type="{display}" serial="SN-1" trigger="OnChange" pin="{pin}" />
+                        </Settings>
+                    </Config>"#
+                )
+            })
+            .collect();
+        format!(
+            "<MobiFlightProject><Outputs>{}</Outputs><Inputs></Inputs></MobiFlightProject>",
+            configs
+        )
+    }
+
+    #[test]
+    fn test_saved_config_keeps_calibration() {
+        let (core, _rx) = Core::new();
+        core.load_config(
+            r#"
+            <MobiFlightProject>
+                <Outputs>
+                    <Config guid="airspeed" active="true">
+                        <Description>Airspeed needle</Description>
+                        <Settings>
+                            <Source type="SimConnect" name="sim/airspeed" />
+                            <Display type="Servo" serial="SN-1" trigger="OnChange" pin="2" />
+                        </Settings>
+                    </Config>
+                </Outputs>
+                <Inputs></Inputs>
+            </MobiFlightProject>
+            "#,
+        )
+        .unwrap();
+        let points = vec![
+            crate::config::CalibrationPoint {
+                value: 0.0,
+                raw: 600.0,
+            },
+            crate::config::CalibrationPoint {
+                value: 200.0,
+                raw: 2400.0,
+            },
+        ];
+        core.set_calibration("airspeed", points.clone()).unwrap();
+
+        let path = std::env::temp_dir().join(format!("openflite-saved-{}.xml", std::process::id()));
+        core.save_config(&path).unwrap();
+        let (reloaded, _rx) = Core::new();
+        reloaded
+            .load_config(&std::fs::read_to_string(&path).unwrap())
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(reloaded.calibration("airspeed"), points);
+    }
+
+    #[test]
+    fn test_switching_profiles() {
+        let dir = std::env::temp_dir().join(format!("openflite-profiles-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("c172.xml"),
+            profile_xml(&[("gear", "Pin", "13"), ("altitude", "7Segment", "0")]),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("a320.xml"),
+            profile_xml(&[("gear", "Pin", "13"), ("master-caution", "Pin", "7")]),
+        )
+        .unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a profile").unwrap();
+
+        let (core, _rx) = Core::new();
+        assert_eq!(core.load_profiles(&dir).unwrap(), vec!["a320", "c172"]);
+
+        let (dev, mock) = MockTransport::device("Panel", "SN-1");
+        core.devices.lock().unwrap().push(dev);
+        let guids = |core: &Core| {
+            core.mapping_engine
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map(|e| e.output_guids())
+        };
+
+        core.activate_profile("c172").unwrap();
+        assert_eq!(core.active_profile().as_deref(), Some("c172"));
+        assert_eq!(guids(&core).unwrap(), vec!["gear", "altitude"]);
+        assert_eq!(mock.written(), "");
+
+        // The altimeter isn't part of the A320 profile, so it gets blanked
+        core.activate_profile("a320").unwrap();
+        assert_eq!(core.active_profile().as_deref(), Some("a320"));
+        assert_eq!(guids(&core).unwrap(), vec!["gear", "master-caution"]);
+        assert_eq!(mock.written(), "15,0,0,        ;");
+
+        // An unknown name changes nothing
+        assert!(matches!(
+            core.activate_profile("b737"),
+            Err(CoreError::ProfileNotFound(_))
+        ));
+        assert_eq!(core.active_profile().as_deref(), Some("a320"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_switching_profiles_defines_bar_glyphs() {
+        let dir = std::env::temp_dir().join(format!("openflite-bars-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("c172.xml"), profile_xml(&[("gear", "Pin", "13")])).unwrap();
+        std::fs::write(
+            dir.join("a320.xml"),
+            profile_xml(&[("fuel", "LCDBar", "0")]),
+        )
+        .unwrap();
+
+        let (core, _rx) = Core::new();
+        core.load_profiles(&dir).unwrap();
+        let (dev, mock) = MockTransport::device("Panel", "SN-1");
+        core.devices.lock().unwrap().push(dev);
+        let glyph_writes = |written: String| written.matches("19,").count();
+
+        core.activate_profile("c172").unwrap();
+        assert_eq!(glyph_writes(mock.written()), 0);
+
+        core.activate_profile("a320").unwrap();
+        assert_eq!(
+            glyph_writes(mock.written()),
+            crate::mapping::bar_glyphs().len()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_scan_probes_ports_in_parallel() {
+        let (core, mut rx) = Core::new();
+        let ports = vec![
+            "/dev/ttyACM0".to_string(),
+            "/dev/ttyS0".to_string(),
+            "/dev/ttyUSB1".to_string(),
+        ];
+
+        let started = std::time::Instant::now();
+        core.probe_ports(ports, |port| {
+            std::thread::sleep(Duration::from_millis(150));
+            let mock = MockTransport::default();
+            match port {
+                "/dev/ttyACM0" => mock.push_incoming("7,Panel,Mega,SN-1,2.0.0;\r\n"),
+                "/dev/ttyS0" => mock.push_incoming("AT+OK\r\n"),
+                _ => {} // silent
+            }
+            MobiFlightDevice::with_transport(Box::new(mock))
+        });
+        assert!(started.elapsed() < Duration::from_millis(400));
+
+        assert_eq!(core.get_devices().len(), 1);
+
+        let mut probed = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            if let Event::PortProbed { port, result } = event {
+                probed.push((port, result));
+            }
+        }
+        assert_eq!(probed.len(), 3);
+        assert_eq!(
+            probed[0],
+            (
+                "/dev/ttyACM0".to_string(),
+                ProbeResult::Detected("SN-1".to_string())
+            )
+        );
+        assert!(matches!(probed[1].1, ProbeResult::Rejected(_)));
+        assert_eq!(
+            probed[2],
+            ("/dev/ttyUSB1".to_string(), ProbeResult::TimedOut)
+        );
+    }
+
+    #[test]
+    fn test_inputs_routed_by_device_serial() {
+        let (core, _rx) = Core::new();
+        core.load_config(
+            r#"
+            <MobiFlightProject>
+                <Outputs></Outputs>
+                <Inputs>
+                    <Config guid="com1-swap" active="true" name="Button1" serial="SN-1">
+                        <Description>COM1 swap</Description>
+                        <Settings>
+                            <Button><OnPress type="XplaneAction" cmd="sim/radios/com1_standy_flip" /></Button>
+                        </Settings>
+                    </Config>
+                    <Config guid="com2-swap" active="true" name="Button1" serial="SN-2">
+                        <Description>COM2 swap</Description>
+                        <Settings>
+                            <Button><OnPress type="XplaneAction" cmd="sim/radios/com2_standy_flip" /></Button>
+                        </Settings>
+                    </Config>
+                </Inputs>
+            </MobiFlightProject>
+            "#,
+        )
+        .unwrap();
+        let sim = MockSimClient::default();
+        core.set_sim_client(Box::new(sim.clone())).unwrap();
+
+        let (dev1, _mock1) = MockTransport::device("Radio", "SN-1");
+        let (dev2, mock2) = MockTransport::device("Radio", "SN-2");
+        core.devices.lock().unwrap().extend([dev1, dev2]);
+
+        mock2.push_incoming("11,Button1,1;\r\n");
+        let responses = core.collect_hardware_events();
+        core.process_simulation_sync(responses);
+
+        assert_eq!(
+            sim.state.lock().unwrap().commands,
+            vec!["sim/radios/com2_standy_flip"]
+        );
+    }
+
+    fn output_config(guid: &str, source: &str, pin: &str) -> crate::config::OutputConfig {
+        crate::config::MobiFlightProject::load(&format!(
+            r#"<MobiFlightProject><Outputs>
+                <Config guid="{guid}" active="true">
+                    <Description>{guid}</Description>
+                    <Settings>
+                        <Source type="SimConnect" name="{source}" />
+                        <Display type="Pin" serial="SN-1" trigger="OnChange" pin="{pin}" />
+                    </Settings>
+                </Config>
+            </Outputs><Inputs></Inputs></MobiFlightProject>"#
+        ))
+        .unwrap()
+        .outputs
+        .config
+        .remove(0)
+    }
+
+    #[test]
+    fn test_partial_updates_keep_other_mappings_state() {
+        let (core, _rx) = Core::new();
+        core.load_config(
+            "<MobiFlightProject><Outputs></Outputs><Inputs></Inputs></MobiFlightProject>",
+        )
+        .unwrap();
+        core.add_output_mapping(output_config("gear", "sim/gear", "13"))
+            .unwrap();
+        let sim = MockSimClient::default();
+        sim.state.lock().unwrap().variables.extend([
+            ("sim/gear".to_string(), 1.0),
+            ("sim/beacon".to_string(), 1.0),
+        ]);
+        core.set_sim_client(Box::new(sim)).unwrap();
+        let (dev, mock) = MockTransport::device("Panel", "SN-1");
+        core.devices.lock().unwrap().push(dev);
+        let frame = || {
+            let actions = core.process_simulation_sync(Vec::new());
+            core.apply_hardware_outputs(actions);
+            let written = mock.written();
+            mock.clear_written();
+            written
+        };
+
+        assert_eq!(frame(), "3,13,1;");
+        // OnChange: nothing new to send
+        assert_eq!(frame(), "");
+
+        // Adding a mapping sends only the new output; gear stays cached
+        core.add_output_mapping(output_config("beacon", "sim/beacon", "7"))
+            .unwrap();
+        assert_eq!(frame(), "3,7,1;");
+        assert!(matches!(
+            core.add_output_mapping(output_config("beacon", "sim/beacon", "7")),
+            Err(CoreError::DuplicateMapping(_))
+        ));
+
+        // Moving the beacon to another pin blanks the old one
+        core.update_mapping(output_config("beacon", "sim/beacon", "8"))
+            .unwrap();
+        assert_eq!(mock.written(), "3,7,0;");
+        mock.clear_written();
+        assert_eq!(frame(), "3,8,1;");
+
+        core.remove_mapping("gear").unwrap();
+        assert_eq!(mock.written(), "3,13,0;");
+        mock.clear_written();
+        assert_eq!(frame(), "");
+        assert!(matches!(
+            core.remove_mapping("gear"),
+            Err(CoreError::MappingNotFound(_))
+        ));
+    }
+
+    fn button_project(cmd: &str) -> crate::config::MobiFlightProject {
+        crate::config::MobiFlightProject::load(&format!(
+            r#"<MobiFlightProject><Inputs>
+                <Config guid="gear-lever" active="true" name="Button 1">
+                    <Description>Gear lever</Description>
+                    <Settings>
+                        <Button><OnPress type="XplaneAction" cmd="{cmd}" /></Button>
+                    </Settings>
+                </Config>
+            </Inputs></MobiFlightProject>"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_bar_glyphs_defined_when_config_changes() {
+        let bar_output = |guid: &str, serial: &str| {
+            let mut config = output_config(guid, "sim/fuel", "0");
+            let display = config.settings.display.as_mut().unwrap();
+            display.display_type = "LCDBar".to_string();
+            display.serial = serial.to_string();
+            config
+        };
+        let glyph_writes = |written: String| written.matches("19,").count();
+        let glyphs = crate::mapping::bar_glyphs().len();
+
+        let (core, _rx) = Core::new();
+        let (dev, mock) = MockTransport::device("Panel", "SN-1");
+        core.devices.lock().unwrap().push(dev);
+        let (other, other_mock) = MockTransport::device("Other", "SN-2");
+        core.devices.lock().unwrap().push(other);
+
+        core.load_config(
+            r#"<MobiFlightProject><Outputs>
+                <Config guid="fuel" active="true">
+                    <Description>Fuel</Description>
+                    <Settings>
+                        <Source type="SimConnect" name="sim/fuel" />
+                        <Display type="LCDBar" serial="SN-1" pin="0" />
+                    </Settings>
+                </Config>
+            </Outputs></MobiFlightProject>"#,
+        )
+        .unwrap();
+        assert_eq!(glyph_writes(mock.written()), glyphs);
+        assert_eq!(glyph_writes(other_mock.written()), 0);
+
+        core.add_output_mapping(bar_output("oil", "SN-2")).unwrap();
+        assert_eq!(glyph_writes(other_mock.written()), glyphs);
+
+        other_mock.clear_written();
+        core.update_mapping(bar_output("oil", "SN-2")).unwrap();
+        assert_eq!(glyph_writes(other_mock.written()), glyphs);
+    }
+
+    #[test]
+    fn test_encoder_steps_in_one_tick_accumulate() {
+        let (core, _rx) = Core::new();
+        core.load_config(
+            r#"<MobiFlightProject><Inputs>
+                <Config guid="hdg" active="true" name="Encoder 1">
+                    <Description>Heading bug</Description>
+                    <Settings>
+                        <Encoder>
+                            <OnRight type="DatarefDelta" dataref="sim/hdg" step="1" min="0" max="359" wrap="true" />
+                        </Encoder>
+                    </Settings>
+                </Config>
+            </Inputs></MobiFlightProject>"#,
+        )
+        .unwrap();
+        let sim = MockSimClient::default();
+        sim.state
+            .lock()
+            .unwrap()
+            .variables
+            .insert("sim/hdg".to_string(), 358.0);
+        core.set_sim_client(Box::new(sim.clone())).unwrap();
+
+        let turn = || {
+            (
+                "SN-1".to_string(),
+                Response::InputEvent {
+                    name: "Encoder 1".to_string(),
+                    value: "1".to_string(),
+                },
+            )
+        };
+        core.process_simulation_sync(vec![turn(), turn(), turn()]);
+        let writes: Vec<f64> = sim
+            .state
+            .lock()
+            .unwrap()
+            .writes
+            .iter()
+            .map(|w| w.1)
+            .collect();
+        assert_eq!(writes, vec![359.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_update_mapping_replaces_input() {
+        let (core, _rx) = Core::new();
+        *core.mapping_engine.lock().unwrap() =
+            Some(MappingEngine::new(button_project("sim/gear_down")));
+        let sim = MockSimClient::default();
+        core.set_sim_client(Box::new(sim.clone())).unwrap();
+        let press = || {
+            core.process_simulation_sync(vec![(
+                "SN-1".to_string(),
+                Response::InputEvent {
+                    name: "Button 1".to_string(),
+                    value: "1".to_string(),
+                },
+            )]);
+        };
+
+        press();
+        let mut replacement = button_project("sim/gear_up");
+        core.update_mapping(replacement.inputs.config.remove(0))
+            .unwrap();
+        press();
+        assert_eq!(
+            sim.state.lock().unwrap().commands,
+            vec!["sim/gear_down", "sim/gear_up"]
+        );
+
+        let mut missing = button_project("sim/gear_up");
+        missing.inputs.config[0].guid = "missing".to_string();
+        assert!(matches!(
+            core.update_mapping(missing.inputs.config.remove(0)),
+            Err(CoreError::MappingNotFound(guid)) if guid == "missing"
+        ));
+    }
+
+    #[test]
+    fn test_on_change_outputs_retried_until_written() {
+        let (core, _rx) = Core::new();
+        core.load_config(PIN_CONFIG).unwrap();
+        let sim = MockSimClient::default();
+        sim.state
+            .lock()
+            .unwrap()
+            .variables
+            .insert("sim/cockpit2/controls/gear_handle_down".to_string(), 1.0);
+        core.set_sim_client(Box::new(sim)).unwrap();
+        let (dev, mock) = MockTransport::device("Panel", "SN-1");
+        core.devices.lock().unwrap().push(dev);
+        let frame = || {
+            let actions = core.process_simulation_sync(Vec::new());
+            core.apply_hardware_outputs(actions);
+        };
+
+        // SN-2 isn't plugged in yet, so its output can't be written
+        frame();
+        assert_eq!(mock.written(), "3,13,1;");
+        mock.clear_written();
+        frame();
+        assert_eq!(mock.written(), "");
+
+        let (late, late_mock) = MockTransport::device("Other", "SN-2");
+        core.devices.lock().unwrap().push(late);
+        frame();
+        assert_eq!(late_mock.written(), "3,7,1;");
+        assert_eq!(mock.written(), "");
+    }
+
+    #[test]
+    fn test_device_infos() {
+        let (core, _rx) = Core::new();
+        let (mut dev, _mock) = MockTransport::device("Panel", "SN-1");
+        dev.port_name = Some("/dev/ttyACM0".to_string());
+        core.devices.lock().unwrap().push(dev);
+
+        assert_eq!(
+            core.get_device_infos(),
+            vec![crate::device::DeviceInfo {
+                name: "Panel".to_string(),
+                board_type: "Mega".to_string(),
+                serial: "SN-1".to_string(),
+                version: "2.0.0".to_string(),
+                port: Some("/dev/ttyACM0".to_string()),
+                connected: true,
+            }]
+        );
+        assert_eq!(core.get_devices(), vec!["Panel (Mega)"]);
+    }
+
+    #[test]
+    fn test_changed_variables_since() {
+        let (core, _rx) = Core::new();
+        let sim = MockSimClient::default();
+        core.set_sim_client(Box::new(sim.clone())).unwrap();
+        let set = |name: &str, value: f64| {
+            sim.state
+                .lock()
+                .unwrap()
+                .variables
+                .insert(name.to_string(), value);
+        };
+        let sorted = |(generation, mut changed): (u64, Vec<(String, f64)>)| {
+            changed.sort_by(|a, b| a.0.cmp(&b.0));
+            (generation, changed)
+        };
+
+        set("sim/altitude", 1000.0);
+        set("sim/heading", 90.0);
+        core.process_simulation_sync(Vec::new());
+        let (first, all) = sorted(core.changed_variables_since(0));
+        assert_eq!(
+            all,
+            vec![
+                ("sim/altitude".to_string(), 1000.0),
+                ("sim/heading".to_string(), 90.0)
+            ]
+        );
+
+        set("sim/altitude", 1100.0);
+        core.process_simulation_sync(Vec::new());
+        let (second, changed) = core.changed_variables_since(first);
+        assert!(second > first);
+        assert_eq!(changed, vec![("sim/altitude".to_string(), 1100.0)]);
+
+        // Nothing changed in between
+        core.process_simulation_sync(Vec::new());
+        assert_eq!(core.changed_variables_since(second), (second, Vec::new()));
+    }
+
+    #[test]
+    fn test_bad_config_is_config_parse_error() {
+        let (core, _rx) = Core::new();
+        let err = core
+            .load_config("<MobiFlightProject><Outputs>")
+            .unwrap_err();
+        assert!(matches!(err, CoreError::ConfigParse(_)));
+    }
+
+    #[test]
+    fn test_missing_device_is_device_not_found() {
+        let (core, _rx) = Core::new();
+        let (dev, _mock) = MockTransport::device("Panel", "SN-1");
+        core.devices.lock().unwrap().push(dev);
+
+        assert!(core.send_command("SN-1", Command::SetPin(13, 1)).is_ok());
+        match core.send_command("SN-404", Command::SetPin(13, 1)) {
+            Err(CoreError::DeviceNotFound(serial)) => assert_eq!(serial, "SN-404"),
+            other => panic!("expected DeviceNotFound, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_heartbeat_sent_after_idle_interval() {
+        let (core, _rx) = Core::with_config(CoreConfig {
+            heartbeat_interval: Some(Duration::from_millis(20)),
+            ..Default::default()
+        });
+        let (dev, mock) = MockTransport::device("Panel", "SN-1");
+        core.devices.lock().unwrap().push(dev);
+
+        core.send_heartbeats();
+        assert_eq!(mock.written(), "");
+
+        std::thread::sleep(Duration::from_millis(30));
+        core.send_heartbeats();
+        assert_eq!(mock.written(), "7;");
+
+        // The ping itself counts as activity
+        core.send_heartbeats();
+        assert_eq!(mock.written(), "7;");
+    }
+
+    #[test]
+    fn test_unanswered_heartbeat_disconnects_board() {
+        let (core, mut rx) = Core::with_config(CoreConfig {
+            heartbeat_interval: Some(Duration::ZERO),
+            ..Default::default()
+        });
+        let (panel, panel_mock) = MockTransport::device("Panel", "SN-1");
+        let (radio, _radio_mock) = MockTransport::device("Radio", "SN-2");
+        core.devices.lock().unwrap().extend([panel, radio]);
+
+        core.send_heartbeats();
+        // Only the panel answers
+        panel_mock.push_incoming("7,Panel,Mega,SN-1,2.0.0;");
+        core.collect_hardware_events();
+        std::thread::sleep(HEARTBEAT_REPLY_TIMEOUT);
+        core.send_heartbeats();
+
+        let connected = |serial: &str| {
+            core.get_device_infos()
+                .iter()
+                .any(|d| d.serial == serial && d.connected)
+        };
+        assert!(connected("SN-1"));
+        assert!(!connected("SN-2"));
+        assert!(matches!(rx.try_recv(), Ok(Event::DeviceDisconnected(serial)) if serial == "SN-2"));
+    }
+
+    #[test]
+    fn test_replugged_board_replaces_dead_entry() {
+        let (core, mut rx) = Core::new();
+        let (dev, _old_mock) = MockTransport::device("Panel", "SN-1");
+        core.add_device(dev);
+        // As left behind by a failed heartbeat
+        core.devices.lock().unwrap()[0].connected = false;
+        while rx.try_recv().is_ok() {}
+
+        core.probe_ports(vec!["/dev/ttyACM0".to_string()], |_| {
+            let mock = MockTransport::default();
+            mock.push_incoming("7,Panel,Mega,SN-1,2.0.0;\r\n");
+            MobiFlightDevice::with_transport(Box::new(mock))
+        });
+
+        let infos = core.get_device_infos();
+        assert_eq!(infos.len(), 1);
+        assert!(infos[0].connected);
+        assert!(std::iter::from_fn(|| rx.try_recv().ok())
+            .any(|e| matches!(e, Event::DeviceDetected(serial) if serial == "SN-1")));
+    }
+
+    #[test]
+    fn test_unplugged_board_stops_button_repeat() {
+        let (core, mut rx) = Core::with_config(CoreConfig {
+            heartbeat_interval: Some(Duration::ZERO),
+            ..Default::default()
+        });
+        let repeat_config = r#"<MobiFlightProject><Inputs>
+                <Config guid="hdg-up" active="true" name="HdgUp">
+                    <Description>Heading up</Description>
+                    <Settings>
+                        <Button repeat="1">
+                            <OnPress type="XplaneAction" cmd="sim/autopilot/heading_up" />
+                        </Button>
+                    </Settings>
+                </Config>
+            </Inputs></MobiFlightProject>"#;
+        core.load_config(repeat_config).unwrap();
+        let sim = MockSimClient::default();
+        core.set_sim_client(Box::new(sim.clone())).unwrap();
+        let (dev, mock) = MockTransport::device("Panel", "SN-1");
+        core.devices.lock().unwrap().push(dev);
+
+        let press = Response::InputEvent {
+            name: "HdgUp".to_string(),
+            value: "1".to_string(),
+        };
+        let commands = || sim.state.lock().unwrap().commands.len();
+
+        // Reloading the config forgets held buttons
+        core.process_simulation_sync(vec![("SN-1".to_string(), press.clone())]);
+        core.load_config(repeat_config).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        core.process_simulation_sync(Vec::new());
+        assert_eq!(commands(), 1);
+
+        core.process_simulation_sync(vec![("SN-1".to_string(), press)]);
+        mock.unplug();
+        core.send_heartbeats();
+        assert!(std::iter::from_fn(|| rx.try_recv().ok())
+            .any(|e| matches!(e, Event::DeviceDisconnected(serial) if serial == "SN-1")));
+
+        std::thread::sleep(Duration::from_millis(5));
+        core.process_simulation_sync(Vec::new());
+        assert_eq!(commands(), 2);
+    }
+
+    #[test]
+    fn test_replacing_sim_client_disconnects_old_one() {
+        let (core, mut rx) = Core::new();
+        let old = MockSimClient::default();
+        core.set_sim_client(Box::new(old.clone())).unwrap();
+        assert!(old.state.lock().unwrap().connected);
+
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(Event::SimConnected { demo: false, .. })
+        ));
+
+        let new = MockSimClient::default();
+        core.set_sim_client(Box::new(new.clone())).unwrap();
+
+        let old_state = old.state.lock().unwrap();
+        assert!(!old_state.connected);
+        assert_eq!(old_state.disconnects, 1);
+        assert!(new.state.lock().unwrap().connected);
+        assert!(matches!(rx.try_recv(), Ok(Event::SimDisconnected)));
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(Event::SimConnected { demo: false, .. })
+        ));
+    }
+
+    #[test]
+    fn test_failed_replacement_restores_old_client() {
+        let (core, mut rx) = Core::new();
+        let old = MockSimClient::default();
+        core.set_sim_client(Box::new(old.clone())).unwrap();
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(Event::SimConnected { demo: false, .. })
+        ));
+
+        let new = MockSimClient::default();
+        new.state.lock().unwrap().fail_connect = true;
+        assert!(core.set_sim_client(Box::new(new.clone())).is_err());
+        assert!(matches!(rx.try_recv(), Ok(Event::SimConnectFailed(_))));
+
+        assert!(old.state.lock().unwrap().connected);
+        assert_eq!(old.state.lock().unwrap().disconnects, 1);
+        old.state
+            .lock()
+            .unwrap()
+            .variables
+            .insert("sim/test".to_string(), 1.0);
+        assert_eq!(core.get_all_variables().get("sim/test"), Some(&1.0));
+        assert!(rx.try_recv().is_err());
+
+        // If the old client won't come back either, the sim is left disconnected
+        old.state.lock().unwrap().fail_connect = true;
+        assert!(core.set_sim_client(Box::new(new)).is_err());
+        assert!(matches!(rx.try_recv(), Ok(Event::SimConnectFailed(_))));
+        assert!(matches!(rx.try_recv(), Ok(Event::SimDisconnected)));
+        assert!(core.sim_client.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_sim_lifecycle_events() {
+        let (core, mut rx) = Core::new();
+
+        core.set_sim_client(Box::new(DummyClient::new())).unwrap();
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(Event::SimConnected { name, demo: true }) if name == "Demo"
+        ));
+
+        core.disconnect_sim();
+        assert!(matches!(rx.try_recv(), Ok(Event::SimDisconnected)));
+        // Nothing left to disconnect
+        core.disconnect_sim();
+        assert!(rx.try_recv().is_err());
+
+        let failing = MockSimClient::default();
+        failing.state.lock().unwrap().fail_connect = true;
+        assert!(core.set_sim_client(Box::new(failing)).is_err());
+        assert!(matches!(rx.try_recv(), Ok(Event::SimConnectFailed(_))));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_fire_output_reaches_target_device() {
+        let (core, _rx) = Core::new();
+        let (panel, panel_mock) = MockTransport::device("Panel", "SN-1");
+        let (radio, radio_mock) = MockTransport::device("Radio", "SN-2");
+        core.devices.lock().unwrap().extend([panel, radio]);
+
+        let display = |display_type: &str, serial: &str| crate::config::Display {
+            display_type: display_type.to_string(),
+            serial: serial.to_string(),
+            pin: "13".to_string(),
+            ..Default::default()
+        };
+        core.fire_output(&display("Pin", "SN-2"), 1.0).unwrap();
+
+        assert_eq!(radio_mock.written(), "3,13,1;");
+        assert_eq!(panel_mock.written(), "");
+        assert!(matches!(
+            core.fire_output(&display("Pin", "SN-404"), 1.0),
+            Err(CoreError::DeviceNotFound(_))
+        ));
+        assert!(matches!(
+            core.fire_output(&display("Pn", "SN-2"), 1.0),
+            Err(CoreError::UnsupportedDisplay(t)) if t == "Pn"
+        ));
+    }
+
+    #[test]
+    fn test_disconnected_device_is_not_written() {
+        let (core, _rx) = Core::new();
+        let (mut dev, mock) = MockTransport::device("Panel", "SN-1");
+        dev.connected = false;
+        core.devices.lock().unwrap().push(dev);
+
+        assert!(matches!(
+            core.set_servo_raw("SN-1", 2, 1500),
+            Err(CoreError::NotConnected)
+        ));
+        assert_eq!(mock.written(), "");
+    }
+
+    #[test]
+    fn test_concurrent_commands_write_whole_frames() {
+        let (core, _rx) = Core::new();
+        let (dev, mock) = MockTransport::device("Panel", "SN-1");
+        // Short writes make write_all loop, which is where frames could split
+        mock.set_max_write(3);
+        core.devices.lock().unwrap().push(dev);
+        let core = Arc::new(core);
+
+        let threads: Vec<_> = (0..8u8)
+            .map(|t| {
+                let core = core.clone();
+                std::thread::spawn(move || {
+                    for i in 0..50u8 {
+                        core.send_command("SN-1", Command::SetPin(t, i)).unwrap();
+                        core.send_command(
+                            "SN-1",
+                            Command::SetLCD(t, 0, format!("thread {} line {}", t, i)),
+                        )
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let written = mock.written();
+        let frames: Vec<&str> = written.split_terminator(';').collect();
+        assert_eq!(frames.len(), 8 * 50 * 2);
+        for frame in frames {
+            let parts: Vec<&str> = frame.split(',').collect();
+            match parts.as_slice() {
+                ["3", pin, value] => {
+                    assert!(pin.parse::<u8>().unwrap() < 8);
+                    assert!(value.parse::<u8>().unwrap() < 50);
+                }
+                ["16", id, "0", text] => {
+                    let line = text.rsplit(' ').next().unwrap();
+                    assert!(line.parse::<u8>().unwrap() < 50);
+                    assert_eq!(*text, format!("thread {} line {}", id, line));
+                }
+                _ => panic!("malformed frame: {:?}", frame),
+            }
+        }
+    }
+
+    const PIN_CONFIG: &str = r#"
+        <MobiFlightProject>
+            <Outputs>
+                <Config guid="gear-led" active="true">
+                    <Description>Gear LED</Description>
+                    <Settings>
+                        <Source type="SimConnect" name="sim/cockpit2/controls/gear_handle_down" />
+                        <Display type="Pin" serial="SN-1" trigger="OnChange" pin="13" />
+                    </Settings>
+                </Config>
+                <Config guid="other-board" active="true">
+                    <Description>Other board</Description>
+                    <Settings>
+                        <Source type="SimConnect" name="sim/cockpit2/controls/gear_handle_down" />
+                        <Display type="Pin" serial="SN-2" trigger="OnChange" pin="7" />
+                    </Settings>
+                </Config>
+            </Outputs>
+            <Inputs></Inputs>
+        </MobiFlightProject>
+    "#;
+
+    #[test]
+    fn test_registered_device_is_initialized() {
+        let (core, mut rx) = Core::new();
+        core.load_config(PIN_CONFIG).unwrap();
+
+        let (dev, mock) = MockTransport::device("Panel", "SN-1");
+        core.add_device(dev);
+
+        assert_eq!(mock.written(), "1;3,13,0;");
+        assert!(matches!(rx.try_recv(), Ok(Event::DeviceDetected(serial)) if serial == "SN-1"));
+    }
+
+    #[test]
+    fn test_device_init_with_lamp_test() {
+        let (core, _rx) = Core::with_config(CoreConfig {
+            lamp_test: Some(Duration::from_millis(1)),
+            ..Default::default()
+        });
+        core.load_config(PIN_CONFIG).unwrap();
+
+        let (dev, mock) = MockTransport::device("Panel", "SN-1");
+        core.add_device(dev);
+
+        assert_eq!(mock.written(), "1;3,13,1;3,13,0;");
+    }
+
+    #[test]
+    fn test_device_init_can_be_disabled() {
+        let (core, _rx) = Core::with_config(CoreConfig {
+            init_on_connect: false,
+            ..Default::default()
+        });
+        core.load_config(PIN_CONFIG).unwrap();
+
+        let (dev, mock) = MockTransport::device("Panel", "SN-1");
+        core.add_device(dev);
+
+        assert_eq!(mock.written(), "");
+    }
+
+    #[test]
+    fn test_init_and_raw_moves_go_through_output_sink() {
+        use crate::mapping::HardwareAction;
+
+        let (core, _rx) = Core::new();
+        core.load_config(PIN_CONFIG).unwrap();
+        let sink = crate::sink::RecordingSink::new();
+        core.set_output_sink(Box::new(sink.clone()));
+
+        let (dev, mock) = MockTransport::device("Panel", "SN-1");
+        core.add_device(dev);
+        core.set_servo_raw("SN-1", 2, 1500).unwrap();
+        core.set_stepper_raw("SN-1", 1, -200).unwrap();
+
+        let serial = "SN-1".to_string();
+        assert_eq!(
+            sink.actions(),
+            vec![
+                HardwareAction::Init {
+                    serial: serial.clone()
+                },
+                HardwareAction::SetPin {
+                    serial: serial.clone(),
+                    pin: 13,
+                    value: 0,
+                },
+                HardwareAction::SetServo {
+                    serial: serial.clone(),
+                    servo_id: 2,
+                    pwm: 1500,
+                },
+                HardwareAction::SetStepper {
+                    serial,
+                    motor_id: 1,
+                    steps: -200,
+                },
+            ]
+        );
+        assert_eq!(mock.written(), "");
     }
 }
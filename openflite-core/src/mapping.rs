@@ -1,98 +1,811 @@
-use crate::config::{Action, MobiFlightProject};
-use crate::protocol::Response;
+use crate::config::{
+    Action, Calibration, CalibrationPoint, Display, InputConfig, InputSettings, MobiFlightProject,
+    OutputConfig,
+};
+use crate::error::{CoreError, Result};
+use crate::protocol::{Command, Response};
+use openflite_connect::SimCapabilities;
 use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Custom-char slots used by `LCDBar`: slot n has its left n pixel columns lit
+pub fn bar_glyphs() -> Vec<(u8, [u8; 8])> {
+    (1..=5u8)
+        .map(|n| {
+            let row = (0x1F << (5 - n)) & 0x1F;
+            (n, [row, row, row, row, row, row, row, 0])
+        })
+        .collect()
+}
+
+/// Actions loading the `bar_glyphs` into the board with `serial`
+pub fn bar_glyph_actions(serial: &str) -> Vec<HardwareAction> {
+    bar_glyphs()
+        .into_iter()
+        .map(|(slot, rows)| HardwareAction::SetLcdCustomChar {
+            serial: serial.to_string(),
+            slot,
+            rows,
+        })
+        .collect()
+}
+
+/// Render `value` as a bar `cells` characters wide using the `bar_glyphs`
+pub fn render_bar(value: f64, min: f64, max: f64, cells: usize) -> String {
+    let fraction = if max > min {
+        ((value - min) / (max - min)).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let mut columns = (fraction * (cells * 5) as f64).round() as usize;
+
+    (0..cells)
+        .map(|_| {
+            let lit = columns.min(5);
+            columns -= lit;
+            if lit == 0 {
+                ' '
+            } else {
+                char::from(lit as u8)
+            }
+        })
+        .collect()
+}
 
 pub struct MappingEngine {
     project: MobiFlightProject,
+    started: Instant,
+    outputs_by_source: HashMap<String, Vec<usize>>,
+    last_sent: HashMap<String, HardwareAction>,
+    unconfirmed: HashMap<String, HardwareAction>,
+    overrides: HashMap<String, f64>,
+    detent_steps: Mutex<HashMap<String, i32>>,
+    repeat_due: Mutex<HashMap<String, (String, Instant)>>,
+    warnings: HashMap<String, Warning>,
+}
+
+const DEFAULT_WARNING_BLINK: Duration = Duration::from_secs(5);
+const WARNING_BLINK_HALF_PERIOD: Duration = Duration::from_millis(250);
+const DEFAULT_WARNING_DEBOUNCE: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum WarningState {
+    #[default]
+    Off,
+    Blinking {
+        since: Instant,
+    },
+    Solid,
+    Acknowledged,
+}
+
+impl WarningState {
+    fn step(self, active: bool, blink: Duration, now: Instant) -> Self {
+        match (self, active) {
+            (_, false) => WarningState::Off,
+            (WarningState::Off, true) => WarningState::Blinking { since: now },
+            (WarningState::Blinking { since }, true) if now.duration_since(since) >= blink => {
+                WarningState::Solid
+            }
+            (state, true) => state,
+        }
+    }
+
+    fn acknowledge(self) -> Self {
+        match self {
+            WarningState::Off => WarningState::Off,
+            _ => WarningState::Acknowledged,
+        }
+    }
+
+    fn lit(self, now: Instant) -> bool {
+        match self {
+            WarningState::Off => false,
+            WarningState::Blinking { since } => {
+                let half_periods =
+                    now.duration_since(since).as_millis() / WARNING_BLINK_HALF_PERIOD.as_millis();
+                half_periods.is_multiple_of(2)
+            }
+            WarningState::Solid | WarningState::Acknowledged => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct Warning {
+    state: WarningState,
+    condition: bool,
+    flipped_at: Option<Instant>,
+}
+
+impl Warning {
+    fn step(self, raw: bool, debounce: Duration, blink: Duration, now: Instant) -> Self {
+        let mut next = self;
+        if raw == self.condition {
+            next.flipped_at = None;
+        } else {
+            let since = *next.flipped_at.get_or_insert(now);
+            if now.duration_since(since) >= debounce {
+                next.condition = raw;
+                next.flipped_at = None;
+            }
+        }
+        next.state = next.state.step(next.condition, blink, now);
+        next
+    }
+}
+
+/// Everything one engine pass produced
+#[derive(Debug, Default)]
+pub struct EngineResult {
+    pub hardware: Vec<HardwareAction>,
+    pub sim: Vec<SimAction>,
 }
 
 impl MappingEngine {
     pub fn new(project: MobiFlightProject) -> Self {
-        Self { project }
+        let outputs_by_source = Self::index_outputs(&project);
+        Self {
+            project,
+            started: Instant::now(),
+            outputs_by_source,
+            last_sent: HashMap::new(),
+            unconfirmed: HashMap::new(),
+            overrides: HashMap::new(),
+            detent_steps: Mutex::new(HashMap::new()),
+            repeat_due: Mutex::new(HashMap::new()),
+            warnings: HashMap::new(),
+        }
+    }
+
+    fn index_outputs(project: &MobiFlightProject) -> HashMap<String, Vec<usize>> {
+        let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, config) in project.outputs.config.iter().enumerate() {
+            let settings = &config.settings;
+            if let (true, Some(source), Some(_)) =
+                (config.active, &settings.source, &settings.display)
+            {
+                index.entry(source.name.clone()).or_default().push(i);
+            }
+        }
+        index
+    }
+
+    /// Values for `type="System"` sources, computed from the clock
+    pub fn system_variables(&self) -> HashMap<String, f64> {
+        Self::system_variables_at(self.started.elapsed())
+    }
+
+    fn system_variables_at(uptime: Duration) -> HashMap<String, f64> {
+        let time_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|t| t.as_millis() % 86_400_000)
+            .unwrap_or(0);
+        let blink = if (uptime.as_millis() / 500).is_multiple_of(2) {
+            1.0
+        } else {
+            0.0
+        };
+
+        HashMap::from([
+            ("system/time_ms".to_string(), time_ms as f64),
+            ("system/uptime_s".to_string(), uptime.as_secs_f64()),
+            ("system/blink_1hz".to_string(), blink),
+        ])
     }
 
     pub fn process_outputs(&self, data: &HashMap<String, f64>) -> Vec<HardwareAction> {
-        let mut actions = Vec::new();
+        self.config_outputs(data)
+            .map(|(_, action)| action)
+            .collect()
+    }
 
-        for config in &self.project.outputs.config {
-            if !config.active {
-                continue;
+    /// `process_outputs` that skips unchanged OnChange outputs and advances warnings
+    pub fn process_changed_outputs(&mut self, data: &HashMap<String, f64>) -> Vec<HardwareAction> {
+        let indexed = self.indexed_outputs(data);
+        let now = Instant::now();
+        for &(i, val) in &indexed {
+            self.step_warning(i, val, now);
+        }
+
+        let outputs: Vec<_> = indexed
+            .into_iter()
+            .filter_map(|(i, val)| {
+                let config = &self.project.outputs.config[i];
+                Some((config, self.output_action(config, val)?))
+            })
+            .map(|(config, action)| {
+                let on_change = config
+                    .settings
+                    .display
+                    .as_ref()
+                    .is_some_and(|d| d.trigger == "OnChange");
+                (on_change.then(|| config.guid.clone()), action)
+            })
+            .collect();
+
+        self.unconfirmed.clear();
+        let mut changed = Vec::new();
+        for (on_change_guid, action) in outputs {
+            match on_change_guid {
+                Some(guid) if self.last_sent.get(&guid) == Some(&action) => {}
+                Some(guid) => {
+                    self.unconfirmed.insert(guid, action.clone());
+                    changed.push(action);
+                }
+                None => changed.push(action),
             }
+        }
+        changed
+    }
 
-            let settings = &config.settings;
-            if let (Some(source), Some(display)) = (&settings.source, &settings.display) {
-                if let Some(&val) = data.get(&source.name) {
-                    let mut final_val = val;
-                    if let Some(comp) = &settings.comparison {
-                        if comp.active {
-                            final_val = self.apply_comparison(val, comp);
-                        }
-                    }
+    /// Record that `sent` reached the hardware
+    pub fn mark_outputs_sent(&mut self, sent: &[HardwareAction]) {
+        let written: Vec<String> = self
+            .unconfirmed
+            .iter()
+            .filter(|(_, action)| sent.contains(action))
+            .map(|(guid, _)| guid.clone())
+            .collect();
+        for guid in written {
+            if let Some(action) = self.unconfirmed.remove(&guid) {
+                self.last_sent.insert(guid, action);
+            }
+        }
+    }
 
-                    match display.display_type.as_str() {
-                        "Pin" => {
-                            actions.push(HardwareAction::SetPin {
-                                serial: display.serial.clone(),
-                                pin: display.pin.parse().unwrap_or(0),
-                                value: final_val as u8,
-                            });
-                        }
-                        "7Segment" => {
-                            actions.push(HardwareAction::Set7Segment {
-                                serial: display.serial.clone(),
-                                module: 0,
-                                index: 0,
-                                value: format!("{:.0}", final_val),
-                            });
-                        }
-                        "LCD" => {
-                            actions.push(HardwareAction::SetLCD {
-                                serial: display.serial.clone(),
-                                display_id: 0,
-                                line: 0,
-                                text: format!("{}: {:.0}", config.description, final_val),
-                            });
-                        }
-                        _ => {}
-                    }
+    /// Make `process_changed_outputs` resend outputs on `serial` (all devices for `None`)
+    pub fn forget_sent_outputs(&mut self, serial: Option<&str>) {
+        self.last_sent
+            .retain(|_, action| serial.is_some_and(|s| action.serial() != s));
+    }
+
+    fn config_outputs<'a>(
+        &'a self,
+        data: &'a HashMap<String, f64>,
+    ) -> impl Iterator<Item = (&'a OutputConfig, HardwareAction)> + 'a {
+        self.project
+            .outputs
+            .config
+            .iter()
+            .filter(|config| config.active)
+            .filter_map(move |config| {
+                let val = match self.overrides.get(&config.guid) {
+                    Some(&val) => val,
+                    None => *data.get(&config.settings.source.as_ref()?.name)?,
+                };
+                Some((config, self.output_action(config, val)?))
+            })
+    }
+
+    /// Outputs for `data` and sim actions for a batch of device events, in one pass
+    pub fn evaluate_all(
+        &self,
+        data: &HashMap<String, f64>,
+        responses: &[(String, Response)],
+    ) -> EngineResult {
+        let hardware = self
+            .indexed_outputs(data)
+            .into_iter()
+            .filter_map(|(i, val)| self.output_action(&self.project.outputs.config[i], val))
+            .collect();
+
+        // Later events see values written by earlier ones, so several encoder
+        // steps in one batch add up
+        let mut sim = Vec::new();
+        let mut written = std::borrow::Cow::Borrowed(data);
+        for (serial, resp) in responses {
+            for action in self.process_inputs(serial, resp, &written) {
+                if let SimAction::WriteDataref(dref, val) = &action {
+                    written.to_mut().insert(dref.clone(), *val);
                 }
+                sim.push(action);
             }
         }
 
-        actions
+        EngineResult { hardware, sim }
+    }
+
+    fn indexed_outputs(&self, data: &HashMap<String, f64>) -> Vec<(usize, f64)> {
+        // Walk whichever side is smaller; the other is a hash lookup
+        let mut matched: Vec<(usize, f64)> = if data.len() < self.outputs_by_source.len() {
+            data.iter()
+                .filter_map(|(name, &val)| Some((self.outputs_by_source.get(name)?, val)))
+                .flat_map(|(indices, val)| indices.iter().map(move |&i| (i, val)))
+                .collect()
+        } else {
+            self.outputs_by_source
+                .iter()
+                .filter_map(|(name, indices)| Some((indices, *data.get(name)?)))
+                .flat_map(|(indices, val)| indices.iter().map(move |&i| (i, val)))
+                .collect()
+        };
+        if !self.overrides.is_empty() {
+            matched.retain(|&(i, _)| {
+                !self
+                    .overrides
+                    .contains_key(&self.project.outputs.config[i].guid)
+            });
+            matched.extend(self.overrides.iter().filter_map(|(guid, &val)| {
+                let i = self.output_index(guid)?;
+                self.project.outputs.config[i].active.then_some((i, val))
+            }));
+        }
+        matched.sort_unstable_by_key(|&(i, _)| i);
+        matched
     }
 
-    pub fn process_inputs(&self, resp: &Response) -> Vec<SimAction> {
+    fn output_action(&self, config: &OutputConfig, val: f64) -> Option<HardwareAction> {
+        let display = config.settings.display.as_ref()?;
+
+        let mut final_val = self.output_value(config, display, val);
+        if display.display_type == "Warning" {
+            // What the light shows from its current state, without moving it on
+            let now = Instant::now();
+            let warning = self.warnings.get(&config.guid).copied().unwrap_or_default();
+            let lit = Self::next_warning(warning, final_val, display, now)
+                .state
+                .lit(now);
+            final_val = if lit { 1.0 } else { 0.0 };
+        }
+
+        Self::display_action(display, &config.description, final_val)
+    }
+
+    fn output_value(&self, config: &OutputConfig, display: &Display, val: f64) -> f64 {
+        let settings = &config.settings;
+        let mut final_val = val;
+        if let Some(comp) = &settings.comparison {
+            if comp.active {
+                final_val = self.apply_comparison(val, comp);
+            }
+        }
+        if let Some(calibration) = &settings.calibration {
+            if Self::is_calibratable(display) {
+                final_val = calibration.interpolate(final_val);
+            }
+        }
+        final_val
+    }
+
+    fn step_warning(&mut self, index: usize, val: f64, now: Instant) {
+        let config = &self.project.outputs.config[index];
+        let Some(display) = config.settings.display.as_ref() else {
+            return;
+        };
+        if display.display_type != "Warning" {
+            return;
+        }
+        let value = self.output_value(config, display, val);
+        let warning = self.warnings.get(&config.guid).copied().unwrap_or_default();
+        let next = Self::next_warning(warning, value, display, now);
+        self.warnings.insert(config.guid.clone(), next);
+    }
+
+    fn next_warning(warning: Warning, value: f64, display: &Display, now: Instant) -> Warning {
+        let blink = display
+            .blink_duration
+            .map_or(DEFAULT_WARNING_BLINK, Duration::from_millis);
+        let debounce = display
+            .debounce
+            .map_or(DEFAULT_WARNING_DEBOUNCE, Duration::from_millis);
+        warning.step(value != 0.0, debounce, blink, now)
+    }
+
+    /// Stop a warning output blinking; false if there is no such warning
+    pub fn acknowledge_warning(&mut self, guid: &str) -> bool {
+        let is_warning = self
+            .project
+            .outputs
+            .config
+            .iter()
+            .find(|c| c.guid == guid)
+            .and_then(|c| c.settings.display.as_ref())
+            .is_some_and(|d| d.display_type == "Warning");
+        if is_warning {
+            if let Some(warning) = self.warnings.get_mut(guid) {
+                warning.state = warning.state.acknowledge();
+            }
+        }
+        is_warning
+    }
+
+    /// The hardware action that shows `value` on `display`
+    pub fn display_action(display: &Display, label: &str, value: f64) -> Option<HardwareAction> {
+        match display.display_type.as_str() {
+            "Pin" | "Warning" => Some(HardwareAction::SetPin {
+                serial: display.serial.clone(),
+                pin: display.pin.parse().unwrap_or(0),
+                value: value as u8,
+            }),
+            "7Segment" => Some(HardwareAction::Set7Segment {
+                serial: display.serial.clone(),
+                module: 0,
+                index: 0,
+                value: Self::display_text(value, display),
+            }),
+            "LCD" => Some(HardwareAction::SetLCD {
+                serial: display.serial.clone(),
+                display_id: 0,
+                address: Self::lcd_address(display),
+                line: 0,
+                text: format!("{}: {}", label, Self::display_text(value, display)),
+            }),
+            "Servo" => Some(HardwareAction::SetServo {
+                serial: display.serial.clone(),
+                servo_id: display.pin.parse().unwrap_or(0),
+                pwm: value.round().clamp(0.0, u16::MAX as f64) as u16,
+            }),
+            "Stepper" => Some(HardwareAction::SetStepper {
+                serial: display.serial.clone(),
+                motor_id: display.pin.parse().unwrap_or(0),
+                steps: value.round() as i32,
+            }),
+            "LCDBar" => Some(HardwareAction::SetLCD {
+                serial: display.serial.clone(),
+                display_id: 0,
+                address: Self::lcd_address(display),
+                line: 0,
+                text: render_bar(
+                    value,
+                    display.min.unwrap_or(0.0),
+                    display.max.unwrap_or(100.0),
+                    display.width.unwrap_or(16),
+                ),
+            }),
+            _ => None,
+        }
+    }
+
+    fn lcd_address(display: &Display) -> Option<u8> {
+        display
+            .address
+            .as_deref()
+            .and_then(|a| match a.strip_prefix("0x") {
+                Some(hex) => u8::from_str_radix(hex, 16).ok(),
+                None => a.parse().ok(),
+            })
+    }
+
+    /// Add an output; false if the guid is already taken
+    pub fn add_output(&mut self, config: OutputConfig) -> bool {
+        if self.output_index(&config.guid).is_some() {
+            return false;
+        }
+        self.project.outputs.config.push(config);
+        self.outputs_by_source = Self::index_outputs(&self.project);
+        true
+    }
+
+    /// Replace the output with the same guid; returns the off commands
+    pub fn update_output(&mut self, config: OutputConfig) -> Option<Vec<HardwareAction>> {
+        let index = self.output_index(&config.guid)?;
+        self.last_sent.remove(&config.guid);
+        self.unconfirmed.remove(&config.guid);
+        self.warnings.remove(&config.guid);
+        let old = std::mem::replace(&mut self.project.outputs.config[index], config);
+        self.outputs_by_source = Self::index_outputs(&self.project);
+        Some(self.orphaned_by(&old))
+    }
+
+    /// Remove the output or input with this guid; returns the off commands
+    pub fn remove_mapping(&mut self, guid: &str) -> Option<Vec<HardwareAction>> {
+        if let Some(index) = self.output_index(guid) {
+            self.last_sent.remove(guid);
+            self.unconfirmed.remove(guid);
+            self.overrides.remove(guid);
+            self.warnings.remove(guid);
+            let old = self.project.outputs.config.remove(index);
+            self.outputs_by_source = Self::index_outputs(&self.project);
+            return Some(self.orphaned_by(&old));
+        }
+
+        let inputs = &mut self.project.inputs.config;
+        let index = inputs.iter().position(|c| c.guid == guid)?;
+        inputs.remove(index);
+        self.forget_input_state(guid);
+        Some(Vec::new())
+    }
+
+    /// Replace the input with the same guid; false if there is no such input
+    pub fn update_input(&mut self, config: InputConfig) -> bool {
+        let inputs = &mut self.project.inputs.config;
+        let Some(index) = inputs.iter().position(|c| c.guid == config.guid) else {
+            return false;
+        };
+        self.forget_input_state(&config.guid);
+        self.project.inputs.config[index] = config;
+        true
+    }
+
+    fn forget_input_state(&self, guid: &str) {
+        self.detent_steps.lock().unwrap().remove(guid);
+        self.repeat_due.lock().unwrap().remove(guid);
+    }
+
+    fn output_index(&self, guid: &str) -> Option<usize> {
+        self.project
+            .outputs
+            .config
+            .iter()
+            .position(|c| c.guid == guid)
+    }
+
+    fn orphaned_by(&self, old: &OutputConfig) -> Vec<HardwareAction> {
+        let still_driven = self.safe_state_actions();
+        old.settings
+            .display
+            .as_ref()
+            .and_then(Self::safe_action)
+            .filter(|action| !still_driven.contains(action))
+            .into_iter()
+            .collect()
+    }
+
+    /// Guids of every output in the config, in config order
+    pub fn output_guids(&self) -> Vec<String> {
+        self.project
+            .outputs
+            .config
+            .iter()
+            .map(|c| c.guid.clone())
+            .collect()
+    }
+
+    /// Sim variables the active outputs read, sorted, without `type="System"` sources
+    pub fn source_variables(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .outputs_by_source
+            .values()
+            .filter_map(|indices| {
+                let source = self.project.outputs.config[indices[0]]
+                    .settings
+                    .source
+                    .as_ref()?;
+                (source.source_type != "System").then(|| source.name.clone())
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Drive an output from `value` instead of its source; `None` clears it
+    pub fn set_override(&mut self, guid: &str, value: Option<f64>) -> Option<Vec<HardwareAction>> {
+        let index = self.output_index(guid)?;
+        self.last_sent.remove(guid);
+        self.unconfirmed.remove(guid);
+        match value {
+            Some(value) => {
+                self.overrides.insert(guid.to_string(), value);
+                Some(Vec::new())
+            }
+            None if self.overrides.remove(guid).is_some() => {
+                let display = self.project.outputs.config[index].settings.display.as_ref();
+                Some(display.and_then(Self::safe_action).into_iter().collect())
+            }
+            None => Some(Vec::new()),
+        }
+    }
+
+    /// Outputs currently overridden, by guid
+    pub fn overrides(&self) -> &HashMap<String, f64> {
+        &self.overrides
+    }
+
+    /// Replace the calibration points of a servo or stepper output
+    pub fn set_calibration(&mut self, guid: &str, points: Vec<CalibrationPoint>) -> Result<()> {
+        let config = self
+            .project
+            .outputs
+            .config
+            .iter_mut()
+            .find(|c| c.guid == guid)
+            .ok_or_else(|| CoreError::MappingNotFound(guid.to_string()))?;
+        match &config.settings.display {
+            Some(display) if Self::is_calibratable(display) => {
+                config.settings.calibration = Some(Calibration { points });
+                Ok(())
+            }
+            display => Err(CoreError::UnsupportedDisplay(
+                display
+                    .as_ref()
+                    .map_or_else(String::new, |d| d.display_type.clone()),
+            )),
+        }
+    }
+
+    fn is_calibratable(display: &Display) -> bool {
+        matches!(display.display_type.as_str(), "Servo" | "Stepper")
+    }
+
+    /// The loaded project, including changes made since, e.g. for saving
+    pub fn project(&self) -> &MobiFlightProject {
+        &self.project
+    }
+
+    pub fn calibration(&self, guid: &str) -> Option<&[CalibrationPoint]> {
+        self.project
+            .outputs
+            .config
+            .iter()
+            .find(|c| c.guid == guid)
+            .and_then(|c| c.settings.calibration.as_ref())
+            .map(|c| c.points.as_slice())
+    }
+
+    /// Off commands for every output in the config, active or not
+    pub fn safe_state_actions(&self) -> Vec<HardwareAction> {
+        self.displays().filter_map(Self::safe_action).collect()
+    }
+
+    fn safe_action(display: &Display) -> Option<HardwareAction> {
+        match display.display_type.as_str() {
+            "Pin" | "Warning" => Some(HardwareAction::SetPin {
+                serial: display.serial.clone(),
+                pin: display.pin.parse().unwrap_or(0),
+                value: 0,
+            }),
+            // Spaces over every digit; an empty string leaves old digits lit
+            "7Segment" => Some(HardwareAction::Set7Segment {
+                serial: display.serial.clone(),
+                module: 0,
+                index: 0,
+                value: " ".repeat(display.width.unwrap_or(8)),
+            }),
+            "LCD" | "LCDBar" => Some(HardwareAction::SetLCD {
+                serial: display.serial.clone(),
+                display_id: 0,
+                address: Self::lcd_address(display),
+                line: 0,
+                text: String::new(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Commands lighting every pin and 7-segment digit in the config
+    pub fn lamp_test_actions(&self) -> Vec<HardwareAction> {
+        self.displays()
+            .filter_map(|display| match display.display_type.as_str() {
+                "Pin" | "Warning" => Some(HardwareAction::SetPin {
+                    serial: display.serial.clone(),
+                    pin: display.pin.parse().unwrap_or(0),
+                    value: 1,
+                }),
+                "7Segment" => Some(HardwareAction::Set7Segment {
+                    serial: display.serial.clone(),
+                    module: 0,
+                    index: 0,
+                    value: "8".repeat(display.width.unwrap_or(8)),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Whether any output on the device draws LCD bar gauges
+    pub fn uses_bar_glyphs(&self, serial: &str) -> bool {
+        self.displays()
+            .any(|d| d.serial == serial && d.display_type == "LCDBar")
+    }
+
+    fn displays(&self) -> impl Iterator<Item = &Display> {
+        self.project
+            .outputs
+            .config
+            .iter()
+            .filter_map(|config| config.settings.display.as_ref())
+    }
+
+    fn display_text(value: f64, display: &Display) -> String {
+        match display.value_map.as_ref().and_then(|map| map.label(value)) {
+            Some(label) => label.to_string(),
+            None => Self::format_value(value, display),
+        }
+    }
+
+    fn format_value(value: f64, display: &Display) -> String {
+        let decimals = display.decimals.unwrap_or(0);
+        let formatted = format!("{:.*}", decimals, value);
+
+        let Some(width) = display.width else {
+            return formatted;
+        };
+
+        let digits = formatted.chars().filter(|&c| c != '.').count();
+        if digits > width {
+            return "-".repeat(width);
+        }
+
+        let padding = width - digits;
+        if display.leading_zeros {
+            let (sign, number) = match formatted.strip_prefix('-') {
+                Some(number) => ("-", number),
+                None => ("", formatted.as_str()),
+            };
+            format!("{}{}{}", sign, "0".repeat(padding), number)
+        } else {
+            let pad = display.pad_char.unwrap_or(' ').to_string();
+            format!("{}{}", pad.repeat(padding), formatted)
+        }
+    }
+
+    /// Turn a hardware event from the board with `serial` into sim actions
+    pub fn process_inputs(
+        &self,
+        serial: &str,
+        resp: &Response,
+        data: &HashMap<String, f64>,
+    ) -> Vec<SimAction> {
         let mut actions = Vec::new();
 
         if let Response::InputEvent { name, value } = resp {
-            // Find input config by name (the hardware pin/device name)
             for config in &self.project.inputs.config {
-                if !config.active || config.description != *name {
+                if !config.active || !config.matches(serial, name) {
                     continue;
                 }
 
                 if let Some(button) = &config.settings.button {
-                    let action = if value == "1" {
+                    let pressed = value == "1";
+                    if button.repeat.is_some_and(|ms| ms > 0) {
+                        let mut due = self.repeat_due.lock().unwrap();
+                        if pressed {
+                            let interval = Duration::from_millis(button.repeat.unwrap_or(0));
+                            due.insert(
+                                config.guid.clone(),
+                                (serial.to_string(), Instant::now() + interval),
+                            );
+                        } else {
+                            due.remove(&config.guid);
+                        }
+                    }
+
+                    let action = if pressed {
                         button.on_press.as_ref()
                     } else {
                         button.on_release.as_ref()
                     };
 
                     if let Some(action) = action {
-                        actions.push(self.create_sim_action(action));
+                        actions.push(self.create_sim_action(action, data));
                     }
                 }
 
                 if let Some(encoder) = &config.settings.encoder {
-                    let action = if value == "0" {
-                        encoder.on_left.as_ref()
-                    } else {
-                        encoder.on_right.as_ref()
+                    // 0 = left, 2 = fast left, 3 = fast right, anything else = right
+                    let (left, fast) = match value.as_str() {
+                        "0" => (true, false),
+                        "2" => (true, true),
+                        "3" => (false, true),
+                        _ => (false, false),
+                    };
+                    let left = left != encoder.reverse;
+
+                    let action = match (left, fast) {
+                        (true, true) => encoder.on_left_fast.as_ref().or(encoder.on_left.as_ref()),
+                        (true, false) => encoder.on_left.as_ref(),
+                        (false, true) => {
+                            encoder.on_right_fast.as_ref().or(encoder.on_right.as_ref())
+                        }
+                        (false, false) => encoder.on_right.as_ref(),
                     };
 
                     if let Some(action) = action {
-                        actions.push(self.create_sim_action(action));
+                        if self.detent_reached(&config.guid, left, encoder.steps_per_detent) {
+                            actions.push(self.create_sim_action(action, data));
+                        }
+                    }
+                }
+
+                if let Some(selector) = &config.settings.selector {
+                    let position = selector
+                        .positions
+                        .iter()
+                        .find(|p| Self::position_matches(&p.value, value));
+
+                    if let Some(position) = position {
+                        actions.push(self.create_sim_action(&position.action, data));
                     }
                 }
             }
@@ -101,7 +814,151 @@ impl MappingEngine {
         actions
     }
 
-    fn create_sim_action(&self, action: &Action) -> SimAction {
+    fn detent_reached(&self, guid: &str, left: bool, steps_per_detent: Option<u32>) -> bool {
+        let steps = steps_per_detent.unwrap_or(1).max(1) as i32;
+        if steps == 1 {
+            return true;
+        }
+
+        let direction = if left { -1 } else { 1 };
+        let mut counts = self.detent_steps.lock().unwrap();
+        let count = counts.entry(guid.to_string()).or_insert(0);
+        if count.signum() == -direction {
+            *count = 0;
+        }
+        *count += direction;
+        if count.abs() >= steps {
+            *count = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// `on_press` again for held buttons whose `repeat` interval has elapsed
+    pub fn repeat_actions(&self, data: &HashMap<String, f64>) -> Vec<SimAction> {
+        self.repeat_actions_at(Instant::now(), data)
+    }
+
+    fn repeat_actions_at(&self, now: Instant, data: &HashMap<String, f64>) -> Vec<SimAction> {
+        let mut due = self.repeat_due.lock().unwrap();
+        let mut actions = Vec::new();
+
+        for config in self.project.inputs.config.iter().filter(|c| c.active) {
+            let Some(button) = &config.settings.button else {
+                continue;
+            };
+            let (Some(repeat), Some(action)) = (button.repeat, &button.on_press) else {
+                continue;
+            };
+            if let Some((_, next)) = due.get_mut(&config.guid) {
+                if *next <= now {
+                    actions.push(self.create_sim_action(action, data));
+                    *next = now + Duration::from_millis(repeat);
+                }
+            }
+        }
+
+        actions
+    }
+
+    /// Forget buttons held on the board with `serial`
+    pub fn release_inputs(&self, serial: &str) {
+        self.repeat_due
+            .lock()
+            .unwrap()
+            .retain(|_, (held_on, _)| held_on != serial);
+    }
+
+    fn position_matches(position: &str, reported: &str) -> bool {
+        match (
+            position.trim().parse::<f64>(),
+            reported.trim().parse::<f64>(),
+        ) {
+            (Ok(a), Ok(b)) => (a - b).abs() < f64::EPSILON,
+            _ => position.trim() == reported.trim(),
+        }
+    }
+
+    /// Capabilities the loaded config needs from the sim backend
+    pub fn required_capabilities(&self) -> SimCapabilities {
+        let mut caps = SimCapabilities::default();
+
+        for config in self.project.outputs.config.iter().filter(|c| c.active) {
+            let Some(source) = &config.settings.source else {
+                continue;
+            };
+            // System sources are computed locally; the rest must be delivered
+            // by the sim once an output asks for them
+            if source.source_type != "System" {
+                caps.subscriptions = true;
+            }
+            if source
+                .value_type
+                .as_deref()
+                .is_some_and(|t| t.eq_ignore_ascii_case("String"))
+            {
+                caps.string_variables = true;
+            }
+        }
+
+        for config in self.project.inputs.config.iter().filter(|c| c.active) {
+            for action in Self::input_actions(&config.settings) {
+                if action.command.is_some() {
+                    caps.commands = true;
+                } else if action.dataref.is_some() {
+                    caps.write_variables = true;
+                }
+            }
+        }
+
+        caps
+    }
+
+    fn input_actions(settings: &InputSettings) -> Vec<&Action> {
+        let mut actions = Vec::new();
+        if let Some(button) = &settings.button {
+            actions.extend(button.on_press.iter().chain(&button.on_release));
+        }
+        if let Some(encoder) = &settings.encoder {
+            actions.extend(
+                encoder
+                    .on_left
+                    .iter()
+                    .chain(&encoder.on_right)
+                    .chain(&encoder.on_left_fast)
+                    .chain(&encoder.on_right_fast),
+            );
+        }
+        if let Some(selector) = &settings.selector {
+            actions.extend(selector.positions.iter().map(|p| &p.action));
+        }
+        actions
+    }
+
+    fn create_sim_action(&self, action: &Action, data: &HashMap<String, f64>) -> SimAction {
+        if action.action_type == "DatarefDelta" {
+            return match &action.dataref {
+                Some(dref) => {
+                    let current = data.get(dref).copied().unwrap_or(action.min.unwrap_or(0.0));
+                    SimAction::WriteDataref(dref.clone(), Self::apply_delta(current, action))
+                }
+                None => SimAction::None,
+            };
+        }
+
+        if action.action_type == "ToggleDataref" {
+            return match &action.dataref {
+                Some(dref) => {
+                    // Not seen from the sim yet: assume off, so the press turns it on
+                    let current = data.get(dref).copied().unwrap_or(0.0);
+                    let toggled = if current != 0.0 { 0.0 } else { 1.0 };
+                    SimAction::WriteDataref(dref.clone(), toggled)
+                }
+                None => SimAction::None,
+            };
+        }
+
         if let Some(cmd) = &action.command {
             SimAction::Command(cmd.clone())
         } else if let Some(dref) = &action.dataref {
@@ -116,6 +973,27 @@ impl MappingEngine {
         }
     }
 
+    fn apply_delta(current: f64, action: &Action) -> f64 {
+        let next = current + action.step.unwrap_or(1.0);
+        match (action.min, action.max) {
+            (Some(min), Some(max)) if action.wrap && (next > max || next < min) => {
+                let span = if min.fract() == 0.0 && max.fract() == 0.0 {
+                    max - min + 1.0
+                } else {
+                    max - min
+                };
+                if span <= 0.0 {
+                    return min;
+                }
+                min + (next - min).rem_euclid(span)
+            }
+            (min, max) => {
+                let next = max.map_or(next, |max| next.min(max));
+                min.map_or(next, |min| next.max(min))
+            }
+        }
+    }
+
     fn apply_comparison(&self, val: f64, comp: &crate::config::Comparison) -> f64 {
         let target: f64 = comp.value.parse().unwrap_or(0.0);
         let condition_met = match comp.operand.as_str() {
@@ -136,6 +1014,26 @@ impl MappingEngine {
     }
 }
 
+/// One output or input config, for `Core::update_mapping`
+#[derive(Debug)]
+pub enum MappingConfig {
+    Output(Box<OutputConfig>),
+    Input(Box<InputConfig>),
+}
+
+impl From<OutputConfig> for MappingConfig {
+    fn from(config: OutputConfig) -> Self {
+        MappingConfig::Output(Box::new(config))
+    }
+}
+
+impl From<InputConfig> for MappingConfig {
+    fn from(config: InputConfig) -> Self {
+        MappingConfig::Input(Box::new(config))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum HardwareAction {
     SetPin {
         serial: String,
@@ -151,6 +1049,8 @@ pub enum HardwareAction {
     SetLCD {
         serial: String,
         display_id: u8,
+        /// I2C address; `None` keeps the original SetLCD format
+        address: Option<u8>,
         line: u8,
         text: String,
     },
@@ -166,10 +1066,1012 @@ pub enum HardwareAction {
         g: u8,
         b: u8,
     },
+    SetServo {
+        serial: String,
+        servo_id: u8,
+        pwm: u16,
+    },
+    /// Reset the board, switching off everything it drives
+    Init {
+        serial: String,
+    },
+    /// Define one of the LCD's eight custom characters
+    SetLcdCustomChar {
+        serial: String,
+        slot: u8,
+        /// One 5-bit pixel row per byte
+        rows: [u8; 8],
+    },
+}
+
+impl HardwareAction {
+    /// Serial of the device this action targets
+    pub fn serial(&self) -> &str {
+        match self {
+            HardwareAction::SetPin { serial, .. }
+            | HardwareAction::Set7Segment { serial, .. }
+            | HardwareAction::SetLCD { serial, .. }
+            | HardwareAction::SetStepper { serial, .. }
+            | HardwareAction::SetRGB { serial, .. }
+            | HardwareAction::SetServo { serial, .. }
+            | HardwareAction::Init { serial }
+            | HardwareAction::SetLcdCustomChar { serial, .. } => serial,
+        }
+    }
+
+    /// The protocol command that carries out this action
+    pub fn to_command(&self) -> Command {
+        match self {
+            HardwareAction::SetPin { pin, value, .. } => Command::SetPin(*pin, *value),
+            HardwareAction::Set7Segment {
+                module,
+                index,
+                value,
+                ..
+            } => Command::Set7Segment(*module, *index, value.clone()),
+            HardwareAction::SetLCD {
+                display_id,
+                address,
+                line,
+                text,
+                ..
+            } => match address {
+                Some(address) => {
+                    Command::SetLCDAddressed(*display_id, *address, *line, text.clone())
+                }
+                None => Command::SetLCD(*display_id, *line, text.clone()),
+            },
+            HardwareAction::SetStepper {
+                motor_id, steps, ..
+            } => Command::SetStepper(*motor_id, *steps),
+            HardwareAction::SetRGB {
+                led_id, r, g, b, ..
+            } => Command::SetRGB(*led_id, *r, *g, *b),
+            HardwareAction::SetServo { servo_id, pwm, .. } => Command::SetServo(*servo_id, *pwm),
+            HardwareAction::Init { .. } => Command::Init,
+            HardwareAction::SetLcdCustomChar { slot, rows, .. } => {
+                Command::SetLcdCustomChar(*slot, *rows)
+            }
+        }
+    }
 }
 
+#[derive(Debug)]
 pub enum SimAction {
     Command(String),
     WriteDataref(String, f64),
     None,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::large_config;
+
+    fn engine(xml: &str) -> MappingEngine {
+        MappingEngine::new(MobiFlightProject::load(xml).unwrap())
+    }
+
+    fn input(name: &str, value: &str) -> Response {
+        Response::InputEvent {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn commands(actions: Vec<SimAction>) -> Vec<String> {
+        actions
+            .into_iter()
+            .filter_map(|a| match a {
+                SimAction::Command(cmd) => Some(cmd),
+                _ => None,
+            })
+            .collect()
+    }
+
+    const SELECTOR_CONFIG: &str = r#"
+        <MobiFlightProject>
+            <Outputs></Outputs>
+            <Inputs>
+                <Config guid="nav-source" active="true">
+                    <Description>NavSource</Description>
+                    <Settings>
+                        <Selector>
+                            <Position value="0">
+                                <Action type="XplaneAction" cmd="sim/gps/nav_source_nav1" />
+                            </Position>
+                            <Position value="1">
+                                <Action type="XplaneAction" cmd="sim/gps/nav_source_nav2" />
+                            </Position>
+                            <Position value="2">
+                                <Action type="XplaneAction" cmd="sim/gps/nav_source_gps" />
+                            </Position>
+                        </Selector>
+                    </Settings>
+                </Config>
+            </Inputs>
+        </MobiFlightProject>
+    "#;
+
+    #[test]
+    fn test_selector_positions_fire_distinct_commands() {
+        let engine = engine(SELECTOR_CONFIG);
+
+        assert_eq!(
+            commands(engine.process_inputs("SN-1", &input("NavSource", "0"), &HashMap::new())),
+            vec!["sim/gps/nav_source_nav1"]
+        );
+        assert_eq!(
+            commands(engine.process_inputs("SN-1", &input("NavSource", "1"), &HashMap::new())),
+            vec!["sim/gps/nav_source_nav2"]
+        );
+        assert_eq!(
+            commands(engine.process_inputs("SN-1", &input("NavSource", "2"), &HashMap::new())),
+            vec!["sim/gps/nav_source_gps"]
+        );
+    }
+
+    fn heading_engine(wrap: bool, step: u32) -> MappingEngine {
+        engine(&format!(
+            r#"
+            <MobiFlightProject>
+                <Outputs></Outputs>
+                <Inputs>
+                    <Config guid="hdg-bug" active="true">
+                        <Description>HeadingBug</Description>
+                        <Settings>
+                            <Encoder>
+                                <OnLeft type="DatarefDelta" dataref="sim/cockpit/autopilot/heading_mag" step="-{step}" min="0" max="359" wrap="{wrap}" />
+                                <OnRight type="DatarefDelta" dataref="sim/cockpit/autopilot/heading_mag" step="{step}" min="0" max="359" wrap="{wrap}" />
+                            </Encoder>
+                        </Settings>
+                    </Config>
+                </Inputs>
+            </MobiFlightProject>
+            "#
+        ))
+    }
+
+    fn written_value(actions: Vec<SimAction>) -> f64 {
+        match actions.as_slice() {
+            [SimAction::WriteDataref(_, value)] => *value,
+            _ => panic!("expected a single dataref write"),
+        }
+    }
+
+    #[test]
+    fn test_dataref_delta_increments_cached_value() {
+        let engine = heading_engine(false, 1);
+        let data = HashMap::from([("sim/cockpit/autopilot/heading_mag".to_string(), 90.0)]);
+        assert_eq!(
+            written_value(engine.process_inputs("SN-1", &input("HeadingBug", "1"), &data)),
+            91.0
+        );
+        assert_eq!(
+            written_value(engine.process_inputs("SN-1", &input("HeadingBug", "0"), &data)),
+            89.0
+        );
+    }
+
+    #[test]
+    fn test_dataref_delta_at_upper_bound() {
+        let data = HashMap::from([("sim/cockpit/autopilot/heading_mag".to_string(), 359.0)]);
+
+        let clamped = heading_engine(false, 1);
+        assert_eq!(
+            written_value(clamped.process_inputs("SN-1", &input("HeadingBug", "1"), &data)),
+            359.0
+        );
+
+        let wrapping = heading_engine(true, 1);
+        assert_eq!(
+            written_value(wrapping.process_inputs("SN-1", &input("HeadingBug", "1"), &data)),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_dataref_delta_wraps_below_lower_bound() {
+        let engine = heading_engine(true, 1);
+        let data = HashMap::from([("sim/cockpit/autopilot/heading_mag".to_string(), 0.0)]);
+        assert_eq!(
+            written_value(engine.process_inputs("SN-1", &input("HeadingBug", "0"), &data)),
+            359.0
+        );
+    }
+
+    #[test]
+    fn test_dataref_delta_wrap_keeps_overshoot() {
+        let engine = heading_engine(true, 10);
+        let at = |heading: f64| {
+            let data = HashMap::from([("sim/cockpit/autopilot/heading_mag".to_string(), heading)]);
+            (
+                written_value(engine.process_inputs("SN-1", &input("HeadingBug", "1"), &data)),
+                written_value(engine.process_inputs("SN-1", &input("HeadingBug", "0"), &data)),
+            )
+        };
+        assert_eq!(at(355.0), (5.0, 345.0));
+        assert_eq!(at(3.0), (13.0, 353.0));
+    }
+
+    #[test]
+    fn test_delta_batch_accumulates() {
+        let engine = heading_engine(false, 1);
+        let data = HashMap::from([("sim/cockpit/autopilot/heading_mag".to_string(), 90.0)]);
+        let turns = vec![
+            ("SN-1".to_string(), input("HeadingBug", "1")),
+            ("SN-1".to_string(), input("HeadingBug", "1")),
+            ("SN-1".to_string(), input("HeadingBug", "1")),
+        ];
+        let written: Vec<f64> = engine
+            .evaluate_all(&data, &turns)
+            .sim
+            .into_iter()
+            .map(|action| written_value(vec![action]))
+            .collect();
+        assert_eq!(written, vec![91.0, 92.0, 93.0]);
+    }
+
+    #[test]
+    fn test_system_blink_toggles_output_at_1hz() {
+        let engine = engine(
+            r#"
+            <MobiFlightProject>
+                <Outputs>
+                    <Config guid="alive" active="true">
+                        <Description>Alive LED</Description>
+                        <Settings>
+                            <Source type="System" name="system/blink_1hz" />
+                            <Display type="Pin" serial="SN-1" trigger="OnChange" pin="13" />
+                        </Settings>
+                    </Config>
+                </Outputs>
+                <Inputs></Inputs>
+            </MobiFlightProject>
+            "#,
+        );
+
+        let pin_at = |ms: u64| {
+            let data = MappingEngine::system_variables_at(Duration::from_millis(ms));
+            match engine.process_outputs(&data).as_slice() {
+                [HardwareAction::SetPin { value, .. }] => *value,
+                _ => panic!("expected a single pin action"),
+            }
+        };
+
+        assert_eq!(pin_at(100), 1);
+        assert_eq!(pin_at(600), 0);
+        assert_eq!(pin_at(1100), 1);
+        assert_eq!(pin_at(1600), 0);
+    }
+
+    fn display(attrs: &str) -> Display {
+        let xml = format!(
+            r#"<Display type="7Segment" serial="SN-1" trigger="OnChange" pin="0" {} />"#,
+            attrs
+        );
+        quick_xml::de::from_str(&xml).unwrap()
+    }
+
+    #[test]
+    fn test_format_zero_padded_integer() {
+        let squawk = display(r#"width="4" leadingZeros="true""#);
+        assert_eq!(MappingEngine::format_value(234.0, &squawk), "0234");
+        assert_eq!(MappingEngine::format_value(7.0, &squawk), "0007");
+        assert_eq!(MappingEngine::format_value(-7.0, &squawk), "-007");
+    }
+
+    #[test]
+    fn test_format_fixed_decimal_frequency() {
+        let com = display(r#"width="5" decimals="2""#);
+        assert_eq!(MappingEngine::format_value(118.0, &com), "118.00");
+        assert_eq!(MappingEngine::format_value(121.5, &com), "121.50");
+
+        let padded = display(r#"width="6" decimals="1" padChar="_""#);
+        assert_eq!(MappingEngine::format_value(99.25, &padded), "___99.2");
+    }
+
+    #[test]
+    fn test_format_overflow_shows_dashes() {
+        let altitude = display(r#"width="4""#);
+        assert_eq!(MappingEngine::format_value(12345.0, &altitude), "----");
+        assert_eq!(MappingEngine::format_value(1234.0, &altitude), "1234");
+    }
+
+    #[test]
+    fn test_format_defaults_match_plain_rounding() {
+        let plain = display("");
+        assert_eq!(MappingEngine::format_value(1050.6, &plain), "1051");
+    }
+
+    #[test]
+    fn test_bar_gauge_rendering() {
+        assert_eq!(render_bar(50.0, 0.0, 100.0, 4), "\u{5}\u{5}  ");
+        assert_eq!(render_bar(30.0, 0.0, 100.0, 4), "\u{5}\u{1}  ");
+        assert_eq!(render_bar(0.0, 0.0, 100.0, 4), "    ");
+        assert_eq!(render_bar(150.0, 0.0, 100.0, 2), "\u{5}\u{5}");
+    }
+
+    #[test]
+    fn test_lcd_bar_display_produces_bar_text() {
+        let engine = engine(
+            r#"
+            <MobiFlightProject>
+                <Outputs>
+                    <Config guid="fuel" active="true">
+                        <Description>Fuel</Description>
+                        <Settings>
+                            <Source type="SimConnect" name="sim/fuel" />
+                            <Display type="LCDBar" serial="SN-1" trigger="OnChange" pin="0" address="0x3F" width="4" min="0" max="200" />
+                        </Settings>
+                    </Config>
+                </Outputs>
+                <Inputs></Inputs>
+            </MobiFlightProject>
+            "#,
+        );
+        let data = HashMap::from([("sim/fuel".to_string(), 100.0)]);
+        assert_eq!(
+            engine.process_outputs(&data),
+            vec![HardwareAction::SetLCD {
+                serial: "SN-1".to_string(),
+                display_id: 0,
+                address: Some(0x3F),
+                line: 0,
+                text: "\u{5}\u{5}  ".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_bar_glyphs() {
+        let glyphs = bar_glyphs();
+        assert_eq!(
+            glyphs[0],
+            (1, [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0])
+        );
+        assert_eq!(
+            glyphs[4],
+            (5, [0x1F, 0x1F, 0x1F, 0x1F, 0x1F, 0x1F, 0x1F, 0])
+        );
+    }
+
+    const SERVO_CONFIG: &str = r#"
+        <MobiFlightProject>
+            <Outputs>
+                <Config guid="airspeed" active="true">
+                    <Description>Airspeed needle</Description>
+                    <Settings>
+                        <Source type="SimConnect" name="sim/airspeed" />
+                        <Display type="Servo" serial="SN-1" trigger="OnChange" pin="2" />
+                    </Settings>
+                </Config>
+                <Config guid="stall-warning" active="true">
+                    <Description>Stall warning</Description>
+                    <Settings>
+                        <Source type="SimConnect" name="sim/stall" />
+                        <Display type="Pin" serial="SN-1" trigger="OnChange" pin="7" />
+                    </Settings>
+                </Config>
+            </Outputs>
+            <Inputs></Inputs>
+        </MobiFlightProject>
+    "#;
+
+    fn servo_pwm(engine: &MappingEngine, airspeed: f64) -> u16 {
+        let data = HashMap::from([("sim/airspeed".to_string(), airspeed)]);
+        match engine.process_outputs(&data).as_slice() {
+            [HardwareAction::SetServo { pwm, .. }] => *pwm,
+            _ => panic!("expected a single servo action"),
+        }
+    }
+
+    #[test]
+    fn test_required_capabilities() {
+        let caps = engine(SERVO_CONFIG).required_capabilities();
+        assert_eq!(
+            caps,
+            SimCapabilities {
+                subscriptions: true,
+                ..Default::default()
+            }
+        );
+
+        let caps = engine(
+            r#"
+            <MobiFlightProject>
+                <Outputs>
+                    <Config guid="clock" active="true">
+                        <Description>Clock</Description>
+                        <Settings>
+                            <Source type="System" name="system/time_ms" />
+                            <Display type="LCD" serial="SN-1" trigger="OnChange" pin="0" />
+                        </Settings>
+                    </Config>
+                    <Config guid="callsign" active="true">
+                        <Description>Callsign</Description>
+                        <Settings>
+                            <Source type="SimConnect" name="ATC ID" valueType="String" />
+                            <Display type="LCD" serial="SN-1" trigger="OnChange" pin="1" />
+                        </Settings>
+                    </Config>
+                </Outputs>
+                <Inputs>
+                    <Config guid="gear" active="true">
+                        <Description>GearToggle</Description>
+                        <Settings>
+                            <Button>
+                                <OnPress type="XplaneAction" cmd="sim/flight_controls/landing_gear_toggle" />
+                            </Button>
+                        </Settings>
+                    </Config>
+                    <Config guid="heading" active="true">
+                        <Description>HeadingDial</Description>
+                        <Settings>
+                            <Encoder>
+                                <OnLeft type="DatarefDelta" dataref="sim/cockpit/autopilot/heading" value="-1" />
+                            </Encoder>
+                        </Settings>
+                    </Config>
+                </Inputs>
+            </MobiFlightProject>
+            "#,
+        )
+        .required_capabilities();
+        assert_eq!(
+            caps,
+            SimCapabilities {
+                write_variables: true,
+                commands: true,
+                subscriptions: true,
+                string_variables: true,
+            }
+        );
+
+        // Inactive outputs don't count
+        let caps = engine(&SERVO_CONFIG.replace(r#"active="true""#, r#"active="false""#))
+            .required_capabilities();
+        assert_eq!(caps, SimCapabilities::default());
+    }
+
+    #[test]
+    fn test_calibration_points_are_stored() {
+        let mut engine = engine(SERVO_CONFIG);
+        assert!(engine.calibration("airspeed").is_none());
+
+        let points = vec![
+            CalibrationPoint {
+                value: 0.0,
+                raw: 544.0,
+            },
+            CalibrationPoint {
+                value: 200.0,
+                raw: 2400.0,
+            },
+        ];
+        engine.set_calibration("airspeed", points.clone()).unwrap();
+        assert_eq!(engine.calibration("airspeed"), Some(points.as_slice()));
+        assert!(matches!(
+            engine.set_calibration("missing", points.clone()),
+            Err(CoreError::MappingNotFound(_))
+        ));
+        assert!(matches!(
+            engine.set_calibration("stall-warning", points),
+            Err(CoreError::UnsupportedDisplay(t)) if t == "Pin"
+        ));
+        assert!(engine.calibration("stall-warning").is_none());
+    }
+
+    #[test]
+    fn test_calibration_interpolates_output() {
+        let mut engine = engine(SERVO_CONFIG);
+        // Uncalibrated: the value goes straight through
+        assert_eq!(servo_pwm(&engine, 1500.0), 1500);
+
+        // Non-linear scale: 0-100 kt over the first half of the dial, 100-200 over the rest
+        engine
+            .set_calibration(
+                "airspeed",
+                vec![
+                    CalibrationPoint {
+                        value: 200.0,
+                        raw: 2400.0,
+                    },
+                    CalibrationPoint {
+                        value: 0.0,
+                        raw: 600.0,
+                    },
+                    CalibrationPoint {
+                        value: 100.0,
+                        raw: 1800.0,
+                    },
+                ],
+            )
+            .unwrap();
+        assert_eq!(servo_pwm(&engine, 50.0), 1200);
+        assert_eq!(servo_pwm(&engine, 150.0), 2100);
+        // Held at the physical stops outside the calibrated range
+        assert_eq!(servo_pwm(&engine, -10.0), 600);
+        assert_eq!(servo_pwm(&engine, 250.0), 2400);
+    }
+
+    #[test]
+    fn test_selector_unknown_position_is_ignored() {
+        let engine = engine(SELECTOR_CONFIG);
+        assert!(engine
+            .process_inputs("SN-1", &input("NavSource", "7"), &HashMap::new())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_evaluate_all_matches_linear_scan() {
+        let mut engine = engine(&large_config(300, 40));
+
+        // Fewer variables than indexed sources, then many more (unrelated ones too)
+        let few: HashMap<String, f64> = (0..10)
+            .map(|i| (format!("var/{}", i * 3), i as f64 * 11.0))
+            .collect();
+        let many: HashMap<String, f64> =
+            (0..500).map(|i| (format!("var/{}", i), i as f64)).collect();
+
+        for data in [few, many, HashMap::new()] {
+            let result = engine.evaluate_all(&data, &[]);
+            assert_eq!(result.hardware, engine.process_outputs(&data));
+            assert!(result.sim.is_empty());
+            // No OnChange outputs here, so the main loop's path sends everything
+            assert_eq!(result.hardware, engine.process_changed_outputs(&data));
+        }
+    }
+
+    #[test]
+    fn test_override_replaces_source() {
+        let mut engine = engine(&large_config(20, 40));
+        let data: HashMap<String, f64> = HashMap::from([("var/2".to_string(), 0.0)]);
+
+        assert_eq!(engine.set_override("out-2", Some(1.0)), Some(Vec::new()));
+        assert_eq!(engine.set_override("out-7", Some(1234.0)), Some(Vec::new()));
+        // Inactive outputs stay off even when overridden
+        engine.set_override("out-5", Some(1.0));
+        assert_eq!(engine.set_override("missing", Some(1.0)), None);
+
+        let expected = vec![
+            HardwareAction::SetPin {
+                serial: "SN-2".to_string(),
+                pin: 2,
+                value: 1,
+            },
+            HardwareAction::Set7Segment {
+                serial: "SN-3".to_string(),
+                module: 0,
+                index: 0,
+                value: " 1234".to_string(),
+            },
+        ];
+        assert_eq!(engine.process_outputs(&data), expected);
+        assert_eq!(engine.evaluate_all(&data, &[]).hardware, expected);
+
+        // Clearing switches the output off until the source says otherwise
+        assert_eq!(
+            engine.set_override("out-2", None),
+            Some(vec![HardwareAction::SetPin {
+                serial: "SN-2".to_string(),
+                pin: 2,
+                value: 0,
+            }])
+        );
+        assert_eq!(engine.set_override("out-2", None), Some(Vec::new()));
+        assert_eq!(engine.process_outputs(&data).len(), 2);
+    }
+
+    #[test]
+    fn test_evaluate_all_processes_responses() {
+        let engine = engine(SELECTOR_CONFIG);
+        let result = engine.evaluate_all(
+            &HashMap::new(),
+            &[
+                ("SN-1".to_string(), input("NavSource", "2")),
+                ("SN-1".to_string(), input("NavSource", "0")),
+            ],
+        );
+        assert!(result.hardware.is_empty());
+        assert_eq!(
+            commands(result.sim),
+            vec!["sim/gps/nav_source_gps", "sim/gps/nav_source_nav1"]
+        );
+    }
+
+    fn ap_mode_config(default: &str) -> String {
+        format!(
+            r#"
+            <MobiFlightProject>
+                <Outputs>
+                    <Config guid="ap-mode" active="true">
+                        <Description>AP</Description>
+                        <Settings>
+                            <Source type="SimConnect" name="sim/cockpit/autopilot/autopilot_mode" />
+                            <Display type="LCD" serial="SN-1" trigger="OnChange" pin="0">
+                                <ValueMap {default}>
+                                    <Entry value="0" label="OFF" />
+                                    <Entry value="1" label="HDG" />
+                                    <Entry value="2" label="NAV" />
+                                    <Entry value="3" label="APR" />
+                                </ValueMap>
+                            </Display>
+                        </Settings>
+                    </Config>
+                </Outputs>
+                <Inputs></Inputs>
+            </MobiFlightProject>
+            "#
+        )
+    }
+
+    fn lcd_text(engine: &MappingEngine, value: f64) -> String {
+        let data = HashMap::from([("sim/cockpit/autopilot/autopilot_mode".to_string(), value)]);
+        match engine.process_outputs(&data).as_slice() {
+            [HardwareAction::SetLCD { text, .. }] => text.clone(),
+            other => panic!("expected one LCD action, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_value_map_labels() {
+        let engine = engine(&ap_mode_config(""));
+        assert_eq!(lcd_text(&engine, 0.0), "AP: OFF");
+        assert_eq!(lcd_text(&engine, 1.0), "AP: HDG");
+        assert_eq!(lcd_text(&engine, 2.0), "AP: NAV");
+        assert_eq!(lcd_text(&engine, 3.0), "AP: APR");
+    }
+
+    #[test]
+    fn test_value_map_fallback() {
+        // No default: unmapped values are shown as numbers
+        let numeric = engine(&ap_mode_config(""));
+        assert_eq!(lcd_text(&numeric, 7.0), "AP: 7");
+
+        let with_default = engine(&ap_mode_config(r#"default="---""#));
+        assert_eq!(lcd_text(&with_default, 7.0), "AP: ---");
+        assert_eq!(lcd_text(&with_default, 1.0), "AP: HDG");
+    }
+
+    #[test]
+    fn test_same_input_name_on_two_boards() {
+        let engine = engine(
+            r#"
+            <MobiFlightProject>
+                <Outputs></Outputs>
+                <Inputs>
+                    <Config guid="com1-swap" active="true" name="Button1" serial="SN-COM1">
+                        <Description>COM1 swap</Description>
+                        <Settings>
+                            <Button><OnPress type="XplaneAction" cmd="sim/radios/com1_standy_flip" /></Button>
+                        </Settings>
+                    </Config>
+                    <Config guid="com2-swap" active="true" name="Button1" serial="SN-COM2">
+                        <Description>COM2 swap</Description>
+                        <Settings>
+                            <Button><OnPress type="XplaneAction" cmd="sim/radios/com2_standy_flip" /></Button>
+                        </Settings>
+                    </Config>
+                    <Config guid="legacy" active="true">
+                        <Description>GearToggle</Description>
+                        <Settings>
+                            <Button><OnPress type="XplaneAction" cmd="sim/flight_controls/landing_gear_toggle" /></Button>
+                        </Settings>
+                    </Config>
+                </Inputs>
+            </MobiFlightProject>
+            "#,
+        );
+        let press = |serial: &str, name: &str| {
+            commands(engine.process_inputs(serial, &input(name, "1"), &HashMap::new()))
+        };
+
+        assert_eq!(
+            press("SN-COM1", "Button1"),
+            vec!["sim/radios/com1_standy_flip"]
+        );
+        assert_eq!(
+            press("SN-COM2", "Button1"),
+            vec!["sim/radios/com2_standy_flip"]
+        );
+        assert!(press("SN-OTHER", "Button1").is_empty());
+        // The description is only a label once a name is set
+        assert!(press("SN-COM1", "COM1 swap").is_empty());
+        // Configs without name/serial still match on description, from any board
+        assert_eq!(
+            press("SN-OTHER", "GearToggle"),
+            vec!["sim/flight_controls/landing_gear_toggle"]
+        );
+    }
+
+    #[test]
+    fn test_toggle_dataref_alternates() {
+        let engine = engine(
+            r#"
+            <MobiFlightProject>
+                <Outputs></Outputs>
+                <Inputs>
+                    <Config guid="landing-light" active="true" name="LandingLight">
+                        <Description>Landing light</Description>
+                        <Settings>
+                            <Button>
+                                <OnPress type="ToggleDataref" dataref="sim/cockpit/electrical/landing_lights_on" />
+                            </Button>
+                        </Settings>
+                    </Config>
+                </Inputs>
+            </MobiFlightProject>
+            "#,
+        );
+        let dref = "sim/cockpit/electrical/landing_lights_on".to_string();
+        let press = |data: &HashMap<String, f64>| {
+            written_value(engine.process_inputs("SN-1", &input("LandingLight", "1"), data))
+        };
+
+        // Nothing cached yet: switch on
+        let mut data = HashMap::new();
+        assert_eq!(press(&data), 1.0);
+
+        // Each press inverts what the sim reported back
+        data.insert(dref.clone(), 1.0);
+        assert_eq!(press(&data), 0.0);
+        data.insert(dref.clone(), 0.0);
+        assert_eq!(press(&data), 1.0);
+        // Any nonzero value counts as on
+        data.insert(dref, 0.75);
+        assert_eq!(press(&data), 0.0);
+
+        // Releasing the button does nothing
+        assert!(engine
+            .process_inputs("SN-1", &input("LandingLight", "0"), &data)
+            .is_empty());
+    }
+
+    fn encoder_engine(attributes: &str, fast: bool) -> MappingEngine {
+        let fast_actions = if fast {
+            r#"<OnLeftFast type="XplaneAction" cmd="sim/radios/com1_coarse_down" />
+               <OnRightFast type="XplaneAction" cmd="sim/radios/com1_coarse_up" />"#
+        } else {
+            ""
+        };
+        engine(&format!(
+            r#"
+            <MobiFlightProject>
+                <Outputs></Outputs>
+                <Inputs>
+                    <Config guid="com1" active="true" name="Encoder1">
+                        <Description>COM1</Description>
+                        <Settings>
+                            <Encoder {attributes}>
+                                <OnLeft type="XplaneAction" cmd="sim/radios/com1_fine_down" />
+                                <OnRight type="XplaneAction" cmd="sim/radios/com1_fine_up" />
+                                {fast_actions}
+                            </Encoder>
+                        </Settings>
+                    </Config>
+                </Inputs>
+            </MobiFlightProject>
+            "#
+        ))
+    }
+
+    fn turn(engine: &MappingEngine, value: &str) -> Vec<String> {
+        commands(engine.process_inputs("SN-1", &input("Encoder1", value), &HashMap::new()))
+    }
+
+    #[test]
+    fn test_encoder_fast_turns() {
+        let engine = encoder_engine("", true);
+        assert_eq!(turn(&engine, "0"), vec!["sim/radios/com1_fine_down"]);
+        assert_eq!(turn(&engine, "1"), vec!["sim/radios/com1_fine_up"]);
+        assert_eq!(turn(&engine, "2"), vec!["sim/radios/com1_coarse_down"]);
+        assert_eq!(turn(&engine, "3"), vec!["sim/radios/com1_coarse_up"]);
+
+        // Without fast actions a fast turn uses the normal one
+        let engine = encoder_engine("", false);
+        assert_eq!(turn(&engine, "3"), vec!["sim/radios/com1_fine_up"]);
+    }
+
+    #[test]
+    fn test_encoder_reverse_and_steps_per_detent() {
+        let engine = encoder_engine(r#"reverse="true""#, false);
+        assert_eq!(turn(&engine, "0"), vec!["sim/radios/com1_fine_up"]);
+
+        let engine = encoder_engine(r#"stepsPerDetent="4""#, false);
+        for _ in 0..3 {
+            assert!(turn(&engine, "1").is_empty());
+        }
+        assert_eq!(turn(&engine, "1"), vec!["sim/radios/com1_fine_up"]);
+        // Turning back part way restarts the count
+        assert!(turn(&engine, "1").is_empty());
+        for _ in 0..3 {
+            assert!(turn(&engine, "0").is_empty());
+        }
+        assert_eq!(turn(&engine, "0"), vec!["sim/radios/com1_fine_down"]);
+    }
+
+    #[test]
+    fn test_button_repeat_while_held() {
+        let engine = engine(
+            r#"
+            <MobiFlightProject>
+                <Outputs></Outputs>
+                <Inputs>
+                    <Config guid="hdg-up" active="true" name="HdgUp">
+                        <Description>Heading up</Description>
+                        <Settings>
+                            <Button repeat="200">
+                                <OnPress type="XplaneAction" cmd="sim/autopilot/heading_up" />
+                            </Button>
+                        </Settings>
+                    </Config>
+                </Inputs>
+            </MobiFlightProject>
+            "#,
+        );
+        let data = HashMap::new();
+        let later = |ms| Instant::now() + Duration::from_millis(ms);
+
+        assert_eq!(
+            commands(engine.process_inputs("SN-1", &input("HdgUp", "1"), &data)),
+            vec!["sim/autopilot/heading_up"]
+        );
+        assert!(engine.repeat_actions_at(later(0), &data).is_empty());
+        assert_eq!(
+            commands(engine.repeat_actions_at(later(250), &data)),
+            vec!["sim/autopilot/heading_up"]
+        );
+        // Rescheduled from the repeat, not fired again straight away
+        assert!(engine.repeat_actions_at(later(300), &data).is_empty());
+
+        engine.process_inputs("SN-1", &input("HdgUp", "0"), &data);
+        assert!(engine.repeat_actions_at(later(1000), &data).is_empty());
+
+        // A board that goes away mid-press never sends the release
+        engine.process_inputs("SN-1", &input("HdgUp", "1"), &data);
+        engine.release_inputs("SN-2");
+        assert!(!engine.repeat_actions_at(later(250), &data).is_empty());
+        engine.release_inputs("SN-1");
+        assert!(engine.repeat_actions_at(later(1000), &data).is_empty());
+    }
+
+    #[test]
+    fn test_warning_blinks_then_goes_solid() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let blink = Duration::from_millis(3000);
+
+        let state = WarningState::Off.step(false, blink, at(0));
+        assert_eq!(state, WarningState::Off);
+        assert!(!state.lit(at(0)));
+
+        // Newly true: blink at 2 Hz, starting lit
+        let state = state.step(true, blink, at(100));
+        assert_eq!(state, WarningState::Blinking { since: at(100) });
+        assert!(state.lit(at(100)));
+        assert!(!state.lit(at(400)));
+        assert!(state.lit(at(650)));
+
+        let state = state.step(true, blink, at(2000));
+        assert!(matches!(state, WarningState::Blinking { .. }));
+
+        // Blink window over
+        let state = state.step(true, blink, at(3100));
+        assert_eq!(state, WarningState::Solid);
+        assert!(state.lit(at(3350)));
+
+        // Cleared, then a new warning blinks again
+        let state = state.step(false, blink, at(4000));
+        assert_eq!(state, WarningState::Off);
+        let state = state.step(true, blink, at(5000));
+        assert_eq!(state, WarningState::Blinking { since: at(5000) });
+    }
+
+    #[test]
+    fn test_warning_acknowledge_cuts_blink_short() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let blink = Duration::from_millis(3000);
+
+        let state = WarningState::Off.step(true, blink, at(0)).acknowledge();
+        assert_eq!(state, WarningState::Acknowledged);
+        assert!(state.lit(at(250)));
+        assert_eq!(state.step(true, blink, at(500)), WarningState::Acknowledged);
+        assert_eq!(state.step(false, blink, at(500)), WarningState::Off);
+
+        // Nothing to acknowledge while off
+        assert_eq!(WarningState::Off.acknowledge(), WarningState::Off);
+    }
+
+    #[test]
+    fn test_warning_ignores_condition_flicker() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let debounce = Duration::from_millis(200);
+        let blink = Duration::from_millis(3000);
+        let step = |warning: Warning, raw, ms| warning.step(raw, debounce, blink, at(ms));
+
+        // A blip shorter than the debounce never lights the warning
+        let warning = step(Warning::default(), true, 0);
+        assert_eq!(warning.state, WarningState::Off);
+        let warning = step(warning, false, 100);
+        assert_eq!(warning, Warning::default());
+
+        // Held long enough, it starts blinking from when it was taken
+        let warning = step(step(warning, true, 200), true, 400);
+        assert_eq!(warning.state, WarningState::Blinking { since: at(400) });
+
+        // Dropping out briefly doesn't restart or clear it
+        let warning = step(step(warning, false, 500), true, 600);
+        assert_eq!(warning.state, WarningState::Blinking { since: at(400) });
+
+        let warning = step(step(warning, false, 700), false, 900);
+        assert_eq!(warning.state, WarningState::Off);
+    }
+
+    #[test]
+    fn test_warning_output_acknowledged_through_engine() {
+        let mut engine = engine(
+            r#"
+            <MobiFlightProject>
+                <Outputs>
+                    <Config guid="master-warning" active="true">
+                        <Description>Master warning</Description>
+                        <Settings>
+                            <Source type="SimConnect" name="sim/cockpit2/annunciators/master_warning" />
+                            <Display type="Warning" serial="SN-1" trigger="OnChange" pin="7" blinkDuration="60000" debounce="0" />
+                        </Settings>
+                    </Config>
+                    <Config guid="gear-led" active="true">
+                        <Description>Gear LED</Description>
+                        <Settings>
+                            <Source type="SimConnect" name="sim/cockpit2/controls/gear_handle_down" />
+                            <Display type="Pin" serial="SN-1" trigger="OnChange" pin="13" />
+                        </Settings>
+                    </Config>
+                </Outputs>
+                <Inputs></Inputs>
+            </MobiFlightProject>
+            "#,
+        );
+        let warning = |value| HardwareAction::SetPin {
+            serial: "SN-1".to_string(),
+            pin: 7,
+            value,
+        };
+        let data = |active: f64| {
+            HashMap::from([(
+                "sim/cockpit2/annunciators/master_warning".to_string(),
+                active,
+            )])
+        };
+
+        assert_eq!(engine.process_changed_outputs(&data(0.0)), vec![warning(0)]);
+
+        // Queries show what the light would do but don't move it on
+        assert_eq!(engine.process_outputs(&data(1.0)), vec![warning(1)]);
+        assert_eq!(
+            engine.evaluate_all(&data(1.0), &[]).hardware,
+            vec![warning(1)]
+        );
+        assert_eq!(engine.warnings["master-warning"].state, WarningState::Off);
+
+        // First frame of a new warning is the lit half of the blink
+        assert_eq!(engine.process_changed_outputs(&data(1.0)), vec![warning(1)]);
+        assert!(matches!(
+            engine.warnings["master-warning"].state,
+            WarningState::Blinking { .. }
+        ));
+
+        assert!(engine.acknowledge_warning("master-warning"));
+        assert_eq!(engine.process_changed_outputs(&data(1.0)), vec![warning(1)]);
+        assert_eq!(
+            engine.warnings["master-warning"].state,
+            WarningState::Acknowledged
+        );
+
+        assert!(!engine.acknowledge_warning("gear-led"));
+        assert!(!engine.acknowledge_warning("missing"));
+    }
+}
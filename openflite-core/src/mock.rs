@@ -0,0 +1,203 @@
+use crate::device::{MobiFlightDevice, Transport};
+use anyhow::{anyhow, Result};
+use openflite_connect::{SimCapabilities, SimClient, VariableMeta};
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+
+/// In-memory transport that records everything written to it
+#[derive(Clone, Default)]
+pub(crate) struct MockTransport {
+    written: Arc<Mutex<Vec<u8>>>,
+    incoming: Arc<Mutex<VecDeque<u8>>>,
+    max_write: Arc<Mutex<Option<usize>>>,
+    flushes: Arc<Mutex<usize>>,
+    hide_pending: Arc<Mutex<bool>>,
+    unplugged: Arc<Mutex<bool>>,
+    closed: Arc<Mutex<bool>>,
+}
+
+impl MockTransport {
+    /// A connected board answering GetInfo with the given name and serial
+    pub fn device(name: &str, serial: &str) -> (MobiFlightDevice, MockTransport) {
+        let mock = MockTransport::default();
+        mock.push_incoming(&format!("7,{},Mega,{},2.0.0;\r\n", name, serial));
+        let dev = MobiFlightDevice::with_transport(Box::new(mock.clone())).unwrap();
+        mock.clear_written();
+        (dev, mock)
+    }
+
+    pub fn push_incoming(&self, data: &str) {
+        self.incoming.lock().unwrap().extend(data.bytes());
+    }
+
+    pub fn written(&self) -> String {
+        String::from_utf8_lossy(&self.written.lock().unwrap()).to_string()
+    }
+
+    /// Accept at most `n` bytes per `write` call, like a busy serial driver
+    pub fn set_max_write(&self, n: usize) {
+        *self.max_write.lock().unwrap() = Some(n);
+    }
+
+    pub fn clear_written(&self) {
+        self.written.lock().unwrap().clear();
+        *self.flushes.lock().unwrap() = 0;
+    }
+
+    /// Make `bytes_to_read` report 0 even with data queued, like some drivers
+    pub fn set_hide_pending(&self, hide: bool) {
+        *self.hide_pending.lock().unwrap() = hide;
+    }
+
+    /// Fail every write from now on, like a board pulled off the USB bus
+    pub fn unplug(&self) {
+        *self.unplugged.lock().unwrap() = true;
+    }
+
+    /// Report end of stream once nothing is queued, like an unplugged port
+    pub fn close_reads(&self) {
+        *self.closed.lock().unwrap() = true;
+    }
+
+    /// Number of `flush` calls since creation or the last `clear_written`
+    pub fn flushes(&self) -> usize {
+        *self.flushes.lock().unwrap()
+    }
+}
+
+impl Read for MockTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut incoming = self.incoming.lock().unwrap();
+        if incoming.is_empty() {
+            if *self.closed.lock().unwrap() {
+                return Ok(0);
+            }
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "no data"));
+        }
+        let n = buf.len().min(incoming.len());
+        for (slot, byte) in buf.iter_mut().zip(incoming.drain(..n)) {
+            *slot = byte;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for MockTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if *self.unplugged.lock().unwrap() {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "unplugged"));
+        }
+        let n = match *self.max_write.lock().unwrap() {
+            Some(max) => buf.len().min(max),
+            None => buf.len(),
+        };
+        self.written.lock().unwrap().extend_from_slice(&buf[..n]);
+        // Let other threads run between partial writes
+        std::thread::yield_now();
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        *self.flushes.lock().unwrap() += 1;
+        Ok(())
+    }
+}
+
+impl Transport for MockTransport {
+    fn bytes_to_read(&self) -> io::Result<u32> {
+        if *self.hide_pending.lock().unwrap() {
+            return Ok(0);
+        }
+        Ok(self.incoming.lock().unwrap().len() as u32)
+    }
+}
+
+/// What a `MockSimClient` has been asked to do
+#[derive(Default)]
+pub(crate) struct MockSimState {
+    pub connected: bool,
+    pub fail_connect: bool,
+    pub disconnects: usize,
+    pub commands: Vec<String>,
+    pub writes: Vec<(String, f64)>,
+    pub variables: HashMap<String, f64>,
+    pub catalog: Vec<VariableMeta>,
+    /// Block in `poll` this long, like a wedged network call
+    pub poll_delay: Option<std::time::Duration>,
+}
+
+/// Sim client whose state is shared with the test through `state`
+#[derive(Clone, Default)]
+pub(crate) struct MockSimClient {
+    pub state: Arc<Mutex<MockSimState>>,
+}
+
+impl SimClient for MockSimClient {
+    fn connect(&mut self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.fail_connect {
+            return Err(anyhow!("connection refused"));
+        }
+        state.connected = true;
+        Ok(())
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.connected = false;
+        state.disconnects += 1;
+        Ok(())
+    }
+
+    fn read_variable(&mut self, variable: &str) -> Result<f64> {
+        self.state
+            .lock()
+            .unwrap()
+            .variables
+            .get(variable)
+            .copied()
+            .ok_or_else(|| anyhow!("Variable {} not found", variable))
+    }
+
+    fn write_variable(&mut self, variable: &str, value: f64) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.writes.push((variable.to_string(), value));
+        state.variables.insert(variable.to_string(), value);
+        Ok(())
+    }
+
+    fn execute_command(&mut self, command: &str) -> Result<()> {
+        self.state
+            .lock()
+            .unwrap()
+            .commands
+            .push(command.to_string());
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Result<()> {
+        let delay = self.state.lock().unwrap().poll_delay;
+        if let Some(delay) = delay {
+            std::thread::sleep(delay);
+        }
+        Ok(())
+    }
+
+    fn get_all_variables(&self) -> HashMap<String, f64> {
+        self.state.lock().unwrap().variables.clone()
+    }
+
+    fn available_variables(&self) -> Vec<VariableMeta> {
+        self.state.lock().unwrap().catalog.clone()
+    }
+
+    fn capabilities(&self) -> SimCapabilities {
+        SimCapabilities {
+            write_variables: true,
+            commands: true,
+            subscriptions: false,
+            string_variables: false,
+        }
+    }
+}
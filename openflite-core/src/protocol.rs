@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 #[derive(Debug, Clone)]
 pub enum Command {
     Init,
@@ -6,11 +8,15 @@ pub enum Command {
     SetName(String),
     GetVersion,
     ResetBoard,
-    SetPin(u8, u8),              // pin, value
-    Set7Segment(u8, u8, String), // module, index, value
-    SetLCD(u8, u8, String),      // display_id, line, text
-    SetStepper(u8, i32),         // motor_id, steps (negative = reverse)
-    SetRGB(u8, u8, u8, u8),      // led_id, r, g, b
+    SetPin(u8, u8),                      // pin, value
+    Set7Segment(u8, u8, String),         // module, index, value
+    SetLCD(u8, u8, String),              // display_id, line, text
+    SetLCDAddressed(u8, u8, u8, String), // display_id, i2c address, line, text
+    SetStepper(u8, i32),                 // motor_id, steps (negative = reverse)
+    SetRGB(u8, u8, u8, u8),              // led_id, r, g, b
+    SetLcdCustomChar(u8, [u8; 8]),       // slot (0-7), one 5-bit row per byte
+    SetServo(u8, u16),                   // servo_id, pulse width
+    GetStatus,
 }
 
 impl Command {
@@ -24,9 +30,12 @@ impl Command {
             Command::GetVersion => 10,
             Command::SetPin(_, _) => 3,
             Command::Set7Segment(_, _, _) => 15,
-            Command::SetLCD(_, _, _) => 16,
+            Command::SetLCD(_, _, _) | Command::SetLCDAddressed(_, _, _, _) => 16,
             Command::SetStepper(_, _) => 17,
             Command::SetRGB(_, _, _, _) => 18,
+            Command::SetLcdCustomChar(_, _) => 19,
+            Command::SetServo(_, _) => 20,
+            Command::GetStatus => 21,
         }
     }
 
@@ -41,17 +50,60 @@ impl Command {
             Command::SetLCD(display_id, line, text) => {
                 format!("{},{},{},{};", id, display_id, line, text)
             }
+            // Firmware with I2C addressing takes the address before the line
+            Command::SetLCDAddressed(display_id, address, line, text) => {
+                format!("{},{},{},{},{};", id, display_id, address, line, text)
+            }
             Command::SetStepper(motor_id, steps) => {
                 format!("{},{},{};", id, motor_id, steps)
             }
             Command::SetRGB(led_id, r, g, b) => {
                 format!("{},{},{},{},{};", id, led_id, r, g, b)
             }
+            Command::SetServo(servo_id, pulse) => format!("{},{},{};", id, servo_id, pulse),
+            Command::SetLcdCustomChar(slot, rows) => {
+                let rows: Vec<String> = rows.iter().map(|r| r.to_string()).collect();
+                format!("{},{},{};", id, slot, rows.join(","))
+            }
             _ => format!("{};", id),
         }
     }
 }
 
+/// Runtime diagnostics a board reports in answer to `GetStatus`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceStatus {
+    /// Bytes of RAM left; boards tend to reset mid-flight when this runs low
+    pub free_ram: u32,
+    /// Checksum of the stored config, to spot corruption between reads
+    pub config_crc: u32,
+    /// Time since the board last started
+    pub uptime: Duration,
+}
+
+impl DeviceStatus {
+    /// Below this much free RAM a board is at risk of resetting
+    pub const LOW_FREE_RAM: u32 = 300;
+
+    pub fn is_low_on_ram(&self) -> bool {
+        self.free_ram < Self::LOW_FREE_RAM
+    }
+
+    /// `21,<free ram bytes>,<config crc, hex or decimal>,<uptime ms>`
+    fn parse(args: &[String]) -> Option<Self> {
+        let crc = args.get(1)?.trim();
+        let config_crc = match crc.strip_prefix("0x").or(crc.strip_prefix("0X")) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => crc.parse().ok()?,
+        };
+        Some(DeviceStatus {
+            free_ram: args.first()?.trim().parse().ok()?,
+            config_crc,
+            uptime: Duration::from_millis(args.get(2)?.trim().parse().ok()?),
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Response {
     Info {
@@ -65,12 +117,13 @@ pub enum Response {
         name: String,
         value: String,
     },
+    Status(DeviceStatus),
     Unknown(u8, Vec<String>),
 }
 
 impl Response {
     pub fn parse(input: &str) -> Option<Self> {
-        let input = input.trim_end_matches(';').trim();
+        let input = input.trim().trim_end_matches(';');
         let parts: Vec<&str> = input.split(',').collect();
         if parts.is_empty() {
             return None;
@@ -90,6 +143,10 @@ impl Response {
                 name: args[0].clone(),
                 value: args[1].clone(),
             }),
+            21 => match DeviceStatus::parse(&args) {
+                Some(status) => Some(Response::Status(status)),
+                None => Some(Response::Unknown(id, args)),
+            },
             _ => Some(Response::Unknown(id, args)),
         }
     }
@@ -103,6 +160,25 @@ mod tests {
         assert_eq!(Command::GetInfo.serialize(), "7;");
         assert_eq!(Command::SetName("Test".to_string()).serialize(), "9,Test;");
         assert_eq!(Command::SetPin(13, 1).serialize(), "3,13,1;");
+        assert_eq!(
+            Command::SetLCD(0, 1, "HDG 270".to_string()).serialize(),
+            "16,0,1,HDG 270;"
+        );
+        assert_eq!(
+            Command::SetLCDAddressed(0, 0x27, 1, "HDG 270".to_string()).serialize(),
+            "16,0,39,1,HDG 270;"
+        );
+        assert_eq!(Command::SetServo(2, 1500).serialize(), "20,2,1500;");
+        assert_eq!(Command::GetStatus.serialize(), "21;");
+    }
+
+    #[test]
+    fn test_custom_char_serialization() {
+        let half_block = [0x1C, 0x1C, 0x1C, 0x1C, 0x1C, 0x1C, 0x1C, 0x00];
+        assert_eq!(
+            Command::SetLcdCustomChar(3, half_block).serialize(),
+            "19,3,28,28,28,28,28,28,28,0;"
+        );
     }
 
     #[test]
@@ -123,4 +199,36 @@ mod tests {
             panic!("Failed to parse info response");
         }
     }
+
+    #[test]
+    fn test_status_parsing() {
+        let Some(Response::Status(status)) = Response::parse("21,1834,0x5A3C,86400123;\r\n") else {
+            panic!("Failed to parse status response");
+        };
+        assert_eq!(
+            status,
+            DeviceStatus {
+                free_ram: 1834,
+                config_crc: 0x5A3C,
+                uptime: Duration::from_millis(86_400_123),
+            }
+        );
+        assert!(!status.is_low_on_ram());
+
+        let Some(Response::Status(status)) = Response::parse("21,212,23100,5000;") else {
+            panic!("Failed to parse decimal CRC");
+        };
+        assert_eq!(status.config_crc, 23100);
+        assert!(status.is_low_on_ram());
+
+        // Truncated or garbled status frames are kept as unknown, not dropped
+        assert!(matches!(
+            Response::parse("21,1834;"),
+            Some(Response::Unknown(21, args)) if args == ["1834"]
+        ));
+        assert!(matches!(
+            Response::parse("21,lots,0x1,5;"),
+            Some(Response::Unknown(21, _))
+        ));
+    }
 }
@@ -0,0 +1,109 @@
+use crate::device::MobiFlightDevice;
+use crate::error::CoreError;
+use crate::mapping::HardwareAction;
+use crate::protocol::Command;
+use std::sync::{Arc, Mutex};
+
+/// Where `Core` sends the hardware actions the mapping engine produces
+pub trait OutputSink: Send + Sync {
+    /// Deliver `actions` for the board with `serial`, in order
+    fn send(&self, serial: &str, actions: &[HardwareAction]) -> Result<(), CoreError>;
+}
+
+/// Writes actions to the connected boards, one serial transaction per call
+pub struct SerialSink {
+    devices: Arc<Mutex<Vec<MobiFlightDevice>>>,
+}
+
+impl SerialSink {
+    /// Sink over `Core`'s device registry, writing under its lock
+    pub(crate) fn new(devices: Arc<Mutex<Vec<MobiFlightDevice>>>) -> Self {
+        Self { devices }
+    }
+}
+
+impl OutputSink for SerialSink {
+    fn send(&self, serial: &str, actions: &[HardwareAction]) -> Result<(), CoreError> {
+        let cmds: Vec<Command> = actions.iter().map(HardwareAction::to_command).collect();
+        let mut devices = self.devices.lock().unwrap();
+        let dev = devices
+            .iter_mut()
+            .find(|d| d.serial == serial)
+            .ok_or_else(|| CoreError::DeviceNotFound(serial.to_string()))?;
+        dev.send_commands(&cmds)
+    }
+}
+
+/// Keeps every action it is sent; clones share the same record
+#[derive(Clone, Default)]
+pub struct RecordingSink {
+    actions: Arc<Mutex<Vec<HardwareAction>>>,
+}
+
+impl RecordingSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything sent so far, in order
+    pub fn actions(&self) -> Vec<HardwareAction> {
+        self.actions.lock().unwrap().clone()
+    }
+
+    pub fn clear(&self) {
+        self.actions.lock().unwrap().clear();
+    }
+}
+
+impl OutputSink for RecordingSink {
+    fn send(&self, _serial: &str, actions: &[HardwareAction]) -> Result<(), CoreError> {
+        self.actions.lock().unwrap().extend_from_slice(actions);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockTransport;
+
+    fn pin(serial: &str, pin: u8, value: u8) -> HardwareAction {
+        HardwareAction::SetPin {
+            serial: serial.to_string(),
+            pin,
+            value,
+        }
+    }
+
+    #[test]
+    fn test_serial_sink_writes_to_matching_device() {
+        let (dev, mock) = MockTransport::device("Panel", "SN-1");
+        let sink = SerialSink::new(Arc::new(Mutex::new(vec![dev])));
+
+        sink.send("SN-1", &[pin("SN-1", 13, 1), pin("SN-1", 7, 0)])
+            .unwrap();
+        assert_eq!(mock.written(), "3,13,1;3,7,0;");
+        assert_eq!(mock.flushes(), 1);
+
+        assert!(matches!(
+            sink.send("SN-2", &[pin("SN-2", 1, 1)]),
+            Err(CoreError::DeviceNotFound(serial)) if serial == "SN-2"
+        ));
+    }
+
+    #[test]
+    fn test_recording_sink_shares_record_between_clones() {
+        let sink = RecordingSink::new();
+        let observer = sink.clone();
+
+        sink.send("SN-1", &[pin("SN-1", 13, 1)]).unwrap();
+        sink.send("SN-2", &[pin("SN-2", 7, 0)]).unwrap();
+        assert_eq!(
+            observer.actions(),
+            vec![pin("SN-1", 13, 1), pin("SN-2", 7, 0)]
+        );
+
+        observer.clear();
+        assert!(sink.actions().is_empty());
+    }
+}
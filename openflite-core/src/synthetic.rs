@@ -0,0 +1,34 @@
+//! Generated configs shared by the unit tests and `benches/evaluate.rs`,
+//! which includes this file directly
+
+/// `count` Pin/7Segment outputs spread over `sources` sim variables and four boards
+pub fn large_config(count: usize, sources: usize) -> String {
+    let outputs: String = (0..count)
+        .map(|i| {
+            let comparison = if i % 3 == 0 {
+                r#"<Comparison active="true" value="50" operand="&gt;" ifValue="1" elseValue="0" />"#
+            } else {
+                ""
+            };
+            format!(
+                r#"<Config guid="out-{i}" active="{active}">
+                    <Description>Output {i}</Description>
+                    <Settings>
+                        <Source type="SimConnect" name="var/{source}" />
+                        {comparison}
+                        <Display type="{display}" serial="SN-{board}" trigger="normal" pin="{pin}" width="5" />
+                    </Settings>
+                </Config>"#,
+                active = i % 5 != 0,
+                source = i % sources,
+                display = if i % 2 == 0 { "Pin" } else { "7Segment" },
+                board = i % 4,
+                pin = i % 50,
+            )
+        })
+        .collect();
+    format!(
+        "<MobiFlightProject><Outputs>{}</Outputs><Inputs></Inputs></MobiFlightProject>",
+        outputs
+    )
+}
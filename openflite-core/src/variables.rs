@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+
+/// Remembers when each sim variable last changed
+#[derive(Default)]
+pub(crate) struct VariableTracker {
+    generation: u64,
+    /// Variable -> (value, generation it was last changed in)
+    entries: HashMap<String, (f64, u64)>,
+}
+
+impl VariableTracker {
+    pub fn record(&mut self, variables: &HashMap<String, f64>) {
+        let next = self.generation + 1;
+        let mut changed = false;
+        for (name, &value) in variables {
+            match self.entries.get_mut(name) {
+                Some((old, _)) if *old == value => {}
+                Some(entry) => {
+                    *entry = (value, next);
+                    changed = true;
+                }
+                None => {
+                    self.entries.insert(name.clone(), (value, next));
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            self.generation = next;
+        }
+    }
+
+    /// Variables changed after `generation`, and the generation to pass next time
+    pub fn changed_since(&self, generation: u64) -> (u64, Vec<(String, f64)>) {
+        let changed = self
+            .entries
+            .iter()
+            .filter(|(_, (_, changed_in))| *changed_in > generation)
+            .map(|(name, (value, _))| (name.clone(), *value))
+            .collect();
+        (self.generation, changed)
+    }
+
+    /// Forget all values (the sim went away) without resetting the generation
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
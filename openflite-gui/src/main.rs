@@ -1,12 +1,15 @@
 use iced::widget::{
-    button, column, container, horizontal_space, pick_list, row, scrollable, text, text_input,
-    vertical_space,
+    button, checkbox, column, container, horizontal_space, pick_list, row, scrollable, slider,
+    text, text_input, vertical_space,
 };
 use iced::{
     executor, Alignment, Application, Color, Command, Element, Length, Settings, Subscription,
     Theme,
 };
-use openflite_core::{Core, Event};
+use openflite_core::config::{Comparison, ConfigSettings, Display, OutputConfig, Source};
+use openflite_core::device::DeviceInfo;
+use openflite_core::protocol::DeviceStatus;
+use openflite_core::{Core, Event, ProbeResult};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
@@ -26,23 +29,45 @@ pub fn main() -> iced::Result {
 }
 
 struct OpenFliteApp {
-    devices: Vec<String>,
+    devices: Vec<DeviceInfo>,
+    /// Ports the last scan looked at but didn't add, with the reason
+    skipped_ports: Vec<(String, String)>,
+    /// Last status each board reported, by serial
+    device_status: HashMap<String, DeviceStatus>,
     error_msg: Option<String>,
     core: Arc<Core>,
     event_rx: Arc<Mutex<Option<mpsc::UnboundedReceiver<Event>>>>,
     is_scanning: bool,
-    sim_status: String,
+    sim_status: SimStatus,
+    /// A sim client is being connected
+    sim_connecting: bool,
+    sim_error: Option<String>,
+    /// Variable names the connected sim can provide, for autocomplete
+    known_variables: Vec<String>,
+    /// Variables the loaded config reads that the sim doesn't list
+    unknown_variables: Vec<String>,
+    /// X-Plane's `DataRefs.txt`, read on connect for discovery; blank to skip
+    datarefs_path: String,
     data_cache: HashMap<String, f64>,
+    /// Last value from `changed_variables_since`
+    data_generation: u64,
     config_loaded: bool,
     // Config Editor State
     show_editor: bool,
     editor: EditorState,
     output_mappings: Vec<OutputMappingDraft>,
+    /// Bench-test values driving output mappings instead of the sim, by index
+    output_overrides: HashMap<usize, f64>,
     input_mappings: Vec<InputMappingDraft>,
     // Flash State
     flash_progress: u8,
     flash_status: Option<String>,
     selected_board: Option<String>,
+    /// Port the board was detected on; flashing goes to the same one
+    flash_port: Option<String>,
+    // Profiles
+    profiles: Vec<String>,
+    active_profile: Option<String>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -59,8 +84,32 @@ struct EditorState {
     input_name: String,
     input_type: Option<String>,
     on_press_cmd: String,
+    on_release_cmd: String,
+    repeat_ms: String,
     on_left_cmd: String,
     on_right_cmd: String,
+    on_left_fast_cmd: String,
+    on_right_fast_cmd: String,
+    steps_per_detent: String,
+    reverse: bool,
+}
+
+/// What the sim card shows as connected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SimStatus {
+    Disconnected,
+    Connected,
+    Demo,
+}
+
+impl SimStatus {
+    fn label(self) -> &'static str {
+        match self {
+            SimStatus::Disconnected => "Disconnected",
+            SimStatus::Connected => "Connected",
+            SimStatus::Demo => "Demo Mode",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -80,17 +129,27 @@ struct InputMappingDraft {
     name: String,
     input_type: String, // "Button" or "Encoder"
     on_press_cmd: String,
+    on_release_cmd: String,
+    repeat_ms: String,
     on_left_cmd: String,
     on_right_cmd: String,
+    on_left_fast_cmd: String,
+    on_right_fast_cmd: String,
+    steps_per_detent: String,
+    reverse: bool,
 }
 
 #[derive(Debug, Clone)]
 enum Message {
     ScanDevices,
     ScanResult(Result<(), String>),
+    ReadDeviceStatus(String),
+    DeviceStatusRead(String, Result<DeviceStatus, String>),
     ConnectSim,
+    DatarefsPathChanged(String),
     DisconnectSim,
-    SimResult(Result<(), String>),
+    /// A connect attempt returned; its outcome arrives as a core event
+    SimConnectFinished,
     ConnectDemo,
     LoadDemoConfig,
     TriggerDemoButton,
@@ -98,6 +157,7 @@ enum Message {
     TriggerEncoderRight,
     CoreEvent(Event),
     Tick,
+    AllOutputsOff,
     // Config Editor Messages
     ToggleEditor,
     EditorDatarefChanged(String),
@@ -105,29 +165,155 @@ enum Message {
     EditorComparisonValueChanged(String),
     EditorIfValueChanged(String),
     EditorElseValueChanged(String),
-    EditorDeviceSelected(String),
+    EditorDeviceSelected(DeviceInfo),
     EditorPinChanged(String),
     EditorDisplayTypeSelected(String),
     AddOutputMapping,
     ApplyMappings,
+    TestOutputMapping(usize),
+    TestOutputMappingDone(usize),
+    OverrideOutput(usize, Option<f64>),
+    AcknowledgeWarning(usize),
     // Input Mapping Messages
     EditorInputNameChanged(String),
     EditorInputTypeSelected(String),
     EditorOnPressCmdChanged(String),
     EditorOnLeftCmdChanged(String),
     EditorOnRightCmdChanged(String),
+    EditorOnReleaseCmdChanged(String),
+    EditorRepeatChanged(String),
+    EditorOnLeftFastCmdChanged(String),
+    EditorOnRightFastCmdChanged(String),
+    EditorStepsPerDetentChanged(String),
+    EditorReverseToggled(bool),
     AddInputMapping,
     // File Persistence
     SaveConfig,
     LoadConfigFile,
     ConfigFileLoaded(Result<(PathBuf, String), String>),
     ConfigFileSaved(Result<PathBuf, String>),
+    // Profiles
+    LoadProfileDir,
+    ProfilesLoaded(Result<Vec<String>, String>),
+    SelectProfile(String),
     // Flash Messages
     SelectBoard(String),
+    DetectBoard,
+    BoardDetected(Result<(String, String), String>), // port, board name
     FlashFirmware,
     FlashComplete(Result<(), String>),
 }
 
+fn output_guid(index: usize) -> String {
+    format!("user-{}", index)
+}
+
+/// The config the generated XML would contain for this draft
+fn output_config(index: usize, m: &OutputMappingDraft) -> OutputConfig {
+    OutputConfig {
+        guid: output_guid(index),
+        active: true,
+        description: m.dataref.clone(),
+        settings: ConfigSettings {
+            source: Some(Source {
+                source_type: "SimConnect".to_string(),
+                name: m.dataref.clone(),
+                value_type: None,
+            }),
+            comparison: Some(Comparison {
+                active: true,
+                value: m.comparison_value.clone(),
+                operand: m.comparison_op.clone(),
+                if_value: m.if_value.clone(),
+                else_value: m.else_value.clone(),
+            }),
+            display: Some(Display {
+                display_type: m.display_type.clone(),
+                serial: m.device.clone(),
+                trigger: "OnChange".to_string(),
+                pin: m.pin.clone(),
+                ..Default::default()
+            }),
+            calibration: None,
+        },
+    }
+}
+
+/// One-line diagnostics for the hardware card, e.g. `RAM 1834 B · CRC 5A3C · up 1d 02:05`
+fn status_summary(status: &DeviceStatus) -> String {
+    let secs = status.uptime.as_secs();
+    let (days, hours, minutes) = (secs / 86_400, secs / 3_600 % 24, secs / 60 % 60);
+    let uptime = if days > 0 {
+        format!("{}d {:02}:{:02}", days, hours, minutes)
+    } else {
+        format!("{:02}:{:02}", hours, minutes)
+    };
+    format!(
+        "RAM {} B{} · CRC {:04X} · up {}",
+        status.free_ram,
+        if status.is_low_on_ram() { " (low)" } else { "" },
+        status.config_crc,
+        uptime
+    )
+}
+
+fn inputs_xml(mappings: &[InputMappingDraft]) -> String {
+    let action = |element: &str, cmd: &str| {
+        if cmd.is_empty() {
+            String::new()
+        } else {
+            format!(r#"<{} type="XplaneAction" cmd="{}" />"#, element, cmd)
+        }
+    };
+
+    let mut inputs_xml = String::new();
+    for (i, m) in mappings.iter().enumerate() {
+        let action_xml = if m.input_type == "Encoder" {
+            let mut attributes = String::new();
+            if let Ok(steps) = m.steps_per_detent.trim().parse::<u32>() {
+                attributes.push_str(&format!(r#" stepsPerDetent="{}""#, steps));
+            }
+            if m.reverse {
+                attributes.push_str(r#" reverse="true""#);
+            }
+            format!(
+                r#"<Encoder{}>
+                    <OnLeft type="XplaneAction" cmd="{}" />
+                    <OnRight type="XplaneAction" cmd="{}" />
+                    {}{}
+                </Encoder>"#,
+                attributes,
+                m.on_left_cmd,
+                m.on_right_cmd,
+                action("OnLeftFast", &m.on_left_fast_cmd),
+                action("OnRightFast", &m.on_right_fast_cmd)
+            )
+        } else {
+            let repeat = match m.repeat_ms.trim().parse::<u64>() {
+                Ok(ms) => format!(r#" repeat="{}""#, ms),
+                Err(_) => String::new(),
+            };
+            format!(
+                r#"<Button{}>
+                    <OnPress type="XplaneAction" cmd="{}" />
+                    {}
+                </Button>"#,
+                repeat,
+                m.on_press_cmd,
+                action("OnRelease", &m.on_release_cmd)
+            )
+        };
+        inputs_xml.push_str(&format!(
+            r#"<Config guid="input-{}" active="true" name="{}">
+                <Description>{}</Description>
+                <Settings>{}</Settings>
+            </Config>"#,
+            i, m.name, m.name, action_xml
+        ));
+    }
+    inputs_xml
+}
+
 impl Application for OpenFliteApp {
     type Message = Message;
     type Theme = Theme;
@@ -142,24 +328,37 @@ impl Application for OpenFliteApp {
         tokio::spawn(async move {
             let _ = core_clone.run().await;
         });
+        core.spawn_watchdog();
 
         (
             Self {
                 devices: Vec::new(),
+                skipped_ports: Vec::new(),
+                device_status: HashMap::new(),
                 error_msg: None,
                 core,
                 event_rx: Arc::new(Mutex::new(Some(event_rx))),
                 is_scanning: false,
-                sim_status: "Disconnected".to_string(),
+                sim_status: SimStatus::Disconnected,
+                sim_connecting: false,
+                sim_error: None,
+                known_variables: Vec::new(),
+                unknown_variables: Vec::new(),
+                datarefs_path: String::new(),
                 data_cache: HashMap::new(),
+                data_generation: 0,
                 config_loaded: false,
                 show_editor: false,
                 editor: EditorState::default(),
                 output_mappings: Vec::new(),
+                output_overrides: HashMap::new(),
                 input_mappings: Vec::new(),
                 flash_progress: 0,
                 flash_status: None,
                 selected_board: None,
+                flash_port: None,
+                profiles: Vec::new(),
+                active_profile: None,
             },
             Command::none(),
         )
@@ -177,17 +376,43 @@ impl Application for OpenFliteApp {
         match message {
             Message::ScanDevices => {
                 self.is_scanning = true;
+                self.skipped_ports.clear();
                 let core = self.core.clone();
                 return Command::perform(
                     async move { core.scan_devices().map_err(|e| e.to_string()) },
                     Message::ScanResult,
                 );
             }
+            Message::ReadDeviceStatus(serial) => {
+                let core = self.core.clone();
+                return Command::perform(
+                    async move {
+                        // Waits on the serial port; keep it off the UI executor
+                        let result = tokio::task::spawn_blocking({
+                            let serial = serial.clone();
+                            move || core.read_device_status(&serial).map_err(|e| e.to_string())
+                        })
+                        .await
+                        .unwrap_or_else(|e| Err(e.to_string()));
+                        (serial, result)
+                    },
+                    |(serial, result)| Message::DeviceStatusRead(serial, result),
+                );
+            }
+            Message::DeviceStatusRead(serial, result) => match result {
+                Ok(status) => {
+                    self.device_status.insert(serial, status);
+                }
+                Err(e) => {
+                    self.device_status.remove(&serial);
+                    self.error_msg = Some(format!("Status from {} failed: {}", serial, e));
+                }
+            },
             Message::ScanResult(result) => {
                 self.is_scanning = false;
                 match result {
                     Ok(_) => {
-                        self.devices = self.core.get_devices();
+                        self.devices = self.core.get_device_infos();
                         self.error_msg = None;
                     }
                     Err(e) => {
@@ -196,94 +421,87 @@ impl Application for OpenFliteApp {
                 }
             }
             Message::CoreEvent(event) => match event {
-                Event::DeviceDetected(_) => {
-                    self.devices = self.core.get_devices();
+                Event::DeviceDetected(_) | Event::DeviceDisconnected(_) => {
+                    self.devices = self.core.get_device_infos();
                 }
-                Event::SimConnected(status) => {
-                    self.sim_status = status;
+                Event::SimConnected { demo, .. } => {
+                    self.sim_connecting = false;
+                    self.sim_status = if demo {
+                        SimStatus::Demo
+                    } else {
+                        SimStatus::Connected
+                    };
+                    self.refresh_variable_catalog();
+                }
+                Event::SimConnectFailed(e) => {
+                    // SimDisconnected follows if the previous client wasn't restored
+                    self.sim_connecting = false;
+                    self.error_msg = Some(format!("Sim connection failed: {}", e));
+                }
+                Event::PortProbed { port, result } => match result {
+                    ProbeResult::Detected(_) => {}
+                    ProbeResult::Rejected(reason) => self.skipped_ports.push((port, reason)),
+                    ProbeResult::TimedOut => {
+                        self.skipped_ports.push((port, "no response".to_string()))
+                    }
+                },
+                Event::Error(msg) => {
+                    self.error_msg = Some(msg);
                 }
                 Event::SimDisconnected => {
-                    self.sim_status = "Disconnected".to_string();
+                    self.sim_status = SimStatus::Disconnected;
                     self.data_cache.clear();
+                    self.data_generation = 0;
+                    self.known_variables.clear();
+                    self.unknown_variables.clear();
                 }
                 _ => {}
             },
             Message::ConnectSim => {
-                self.sim_status = "Connecting...".to_string();
+                self.sim_connecting = true;
                 let core = self.core.clone();
+                let datarefs_path = self.datarefs_path.trim().to_string();
                 return Command::perform(
                     async move {
-                        let client = Box::new(openflite_connect::xplane::XPlaneClient::new(
-                            "127.0.0.1:49000",
-                        ));
-                        let res = core.set_sim_client(client).map_err(|e| e.to_string());
-                        if res.is_ok() {
-                            core.broadcast(Event::SimConnected("Connected".to_string()));
+                        let mut client =
+                            openflite_connect::xplane::XPlaneClient::new("127.0.0.1:49000");
+                        if !datarefs_path.is_empty() {
+                            client = client.with_datarefs_file(datarefs_path);
                         }
-                        res
+                        // Reported through SimConnected / SimConnectFailed
+                        let _ = core.set_sim_client(Box::new(client));
                     },
-                    Message::SimResult,
+                    |_| Message::SimConnectFinished,
                 );
             }
+            Message::DatarefsPathChanged(path) => {
+                self.datarefs_path = path;
+            }
             Message::DisconnectSim => {
                 self.core.disconnect_sim();
             }
-            Message::SimResult(result) => {
-                if let Err(e) = result {
-                    self.sim_status = format!("Error: {}", e);
-                }
-            }
+            Message::SimConnectFinished => {}
             Message::ConnectDemo => {
-                self.sim_status = "Demo Mode".to_string();
+                self.sim_connecting = true;
                 let core = self.core.clone();
                 return Command::perform(
                     async move {
                         let client = Box::new(openflite_connect::dummy::DummyClient::new());
-                        let res = core.set_sim_client(client).map_err(|e| e.to_string());
-                        if res.is_ok() {
-                            core.broadcast(Event::SimConnected("Demo Mode".to_string()));
-                        }
-                        res
+                        let _ = core.set_sim_client(client);
                     },
-                    Message::SimResult,
+                    |_| Message::SimConnectFinished,
                 );
             }
             Message::LoadDemoConfig => {
-                let xml = r#"
-                    <MobiFlightProject>
-                        <Outputs>
-                            <Config guid="demo-altitude" active="true">
-                                <Description>Altitude LED</Description>
-                                <Settings>
-                                    <Source type="SimConnect" name="sim/flightmodel/position/altitude" />
-                                    <Comparison active="true" value="1050" operand=">" ifValue="1" elseValue="0" />
-                                    <Display type="Pin" serial="DEMO-BOARD" trigger="OnChange" pin="13" />
-                                </Settings>
-                            </Config>
-                        </Outputs>
-                        <Inputs>
-                            <Config guid="demo-gear" active="true">
-                                <Description>GearToggle</Description>
-                                <Settings>
-                                    <Button>
-                                        <OnPress type="XplaneAction" cmd="sim/annunciator/gear_unsafe" />
-                                    </Button>
-                                </Settings>
-                            </Config>
-                            <Config guid="demo-heading" active="true">
-                                <Description>HeadingDial</Description>
-                                <Settings>
-                                    <Encoder>
-                                        <OnLeft type="XplaneAction" cmd="sim/autopilot/heading_down" />
-                                        <OnRight type="XplaneAction" cmd="sim/autopilot/heading_up" />
-                                    </Encoder>
-                                </Settings>
-                            </Config>
-                        </Inputs>
-                    </MobiFlightProject>
-                "#;
-                if self.core.load_config(xml).is_ok() {
+                if self
+                    .core
+                    .load_config(openflite_core::config::DEMO_CONFIG)
+                    .is_ok()
+                {
+                    self.active_profile = None;
                     self.config_loaded = true;
+                    self.output_overrides.clear();
+                    self.refresh_variable_catalog();
                     self.error_msg = None;
                 } else {
                     self.error_msg = Some("Failed to load demo config".to_string());
@@ -320,7 +538,13 @@ impl Application for OpenFliteApp {
                 );
             }
             Message::Tick => {
-                self.data_cache = self.core.get_all_variables();
+                let (generation, changed) = self.core.changed_variables_since(self.data_generation);
+                self.data_generation = generation;
+                self.data_cache.extend(changed);
+                self.sim_error = self.core.sim_last_error();
+            }
+            Message::AllOutputsOff => {
+                self.core.all_outputs_off();
             }
             // Config Editor Message Handlers
             Message::ToggleEditor => {
@@ -341,8 +565,8 @@ impl Application for OpenFliteApp {
             Message::EditorElseValueChanged(val) => {
                 self.editor.else_value = val;
             }
-            Message::EditorDeviceSelected(val) => {
-                self.editor.target_device = Some(val);
+            Message::EditorDeviceSelected(device) => {
+                self.editor.target_device = Some(device.serial);
             }
             Message::EditorPinChanged(val) => {
                 self.editor.target_pin = val;
@@ -367,17 +591,75 @@ impl Application for OpenFliteApp {
                             .unwrap_or("Pin".to_string()),
                     });
                     self.editor = EditorState::default();
+
+                    // Apply straight away when a config is running; otherwise
+                    // it goes in with the next APPLY
+                    if self.config_loaded {
+                        let index = self.output_mappings.len() - 1;
+                        let draft = &self.output_mappings[index];
+                        let result = match self.core.add_output_mapping(output_config(index, draft))
+                        {
+                            Err(openflite_core::CoreError::DuplicateMapping(_)) => {
+                                self.core.update_mapping(output_config(index, draft))
+                            }
+                            other => other,
+                        };
+                        if let Err(e) = result {
+                            self.error_msg = Some(format!("Failed to add mapping: {}", e));
+                        }
+                    }
                 }
             }
             Message::ApplyMappings => {
                 let xml = self.generate_config_xml();
                 if self.core.load_config(&xml).is_ok() {
+                    self.active_profile = None;
                     self.config_loaded = true;
+                    self.output_overrides.clear();
+                    self.refresh_variable_catalog();
                     self.error_msg = None;
                 } else {
                     self.error_msg = Some("Failed to apply config".to_string());
                 }
             }
+            Message::TestOutputMapping(i) => {
+                if let Some(m) = self.output_mappings.get(i) {
+                    let value = m.if_value.parse().unwrap_or(1.0);
+                    if let Err(e) = self.fire_test_output(m, value) {
+                        self.error_msg = Some(format!("Test failed: {}", e));
+                    }
+                    return Command::perform(
+                        tokio::time::sleep(tokio::time::Duration::from_secs(1)),
+                        move |_| Message::TestOutputMappingDone(i),
+                    );
+                }
+            }
+            Message::TestOutputMappingDone(i) => {
+                if let Some(m) = self.output_mappings.get(i) {
+                    let value = m.else_value.parse().unwrap_or(0.0);
+                    if let Err(e) = self.fire_test_output(m, value) {
+                        self.error_msg = Some(format!("Test failed: {}", e));
+                    }
+                }
+            }
+            Message::OverrideOutput(i, value) => {
+                match self.core.set_output_override(&output_guid(i), value) {
+                    Ok(()) => match value {
+                        Some(value) => {
+                            self.output_overrides.insert(i, value);
+                        }
+                        None => {
+                            self.output_overrides.remove(&i);
+                        }
+                    },
+                    Err(e) => self.error_msg = Some(format!("Override failed: {}", e)),
+                }
+            }
+            Message::AcknowledgeWarning(i) => {
+                if let Err(e) = self.core.acknowledge_warning(&output_guid(i)) {
+                    self.error_msg = Some(format!("Acknowledge failed: {}", e));
+                }
+            }
             // Input Mapping Handlers
             Message::EditorInputNameChanged(val) => {
                 self.editor.input_name = val;
@@ -394,6 +676,24 @@ impl Application for OpenFliteApp {
             Message::EditorOnRightCmdChanged(val) => {
                 self.editor.on_right_cmd = val;
             }
+            Message::EditorOnReleaseCmdChanged(val) => {
+                self.editor.on_release_cmd = val;
+            }
+            Message::EditorRepeatChanged(val) => {
+                self.editor.repeat_ms = val;
+            }
+            Message::EditorOnLeftFastCmdChanged(val) => {
+                self.editor.on_left_fast_cmd = val;
+            }
+            Message::EditorOnRightFastCmdChanged(val) => {
+                self.editor.on_right_fast_cmd = val;
+            }
+            Message::EditorStepsPerDetentChanged(val) => {
+                self.editor.steps_per_detent = val;
+            }
+            Message::EditorReverseToggled(val) => {
+                self.editor.reverse = val;
+            }
             Message::AddInputMapping => {
                 if !self.editor.input_name.is_empty() {
                     self.input_mappings.push(InputMappingDraft {
@@ -404,15 +704,27 @@ impl Application for OpenFliteApp {
                             .clone()
                             .unwrap_or("Button".to_string()),
                         on_press_cmd: self.editor.on_press_cmd.clone(),
+                        on_release_cmd: self.editor.on_release_cmd.clone(),
+                        repeat_ms: self.editor.repeat_ms.clone(),
                         on_left_cmd: self.editor.on_left_cmd.clone(),
                         on_right_cmd: self.editor.on_right_cmd.clone(),
+                        on_left_fast_cmd: self.editor.on_left_fast_cmd.clone(),
+                        on_right_fast_cmd: self.editor.on_right_fast_cmd.clone(),
+                        steps_per_detent: self.editor.steps_per_detent.clone(),
+                        reverse: self.editor.reverse,
                     });
                     // Reset input fields
                     self.editor.input_name = String::new();
                     self.editor.input_type = None;
                     self.editor.on_press_cmd = String::new();
+                    self.editor.on_release_cmd = String::new();
+                    self.editor.repeat_ms = String::new();
                     self.editor.on_left_cmd = String::new();
                     self.editor.on_right_cmd = String::new();
+                    self.editor.on_left_fast_cmd = String::new();
+                    self.editor.on_right_fast_cmd = String::new();
+                    self.editor.steps_per_detent = String::new();
+                    self.editor.reverse = false;
                 }
             }
             // File Persistence Handlers
@@ -468,10 +780,53 @@ impl Application for OpenFliteApp {
                 }
                 _ => {}
             },
+            Message::LoadProfileDir => {
+                let core = self.core.clone();
+                return Command::perform(
+                    async move {
+                        let folder = rfd::AsyncFileDialog::new()
+                            .set_title("Profile folder")
+                            .pick_folder()
+                            .await;
+                        match folder {
+                            Some(folder) => {
+                                core.load_profiles(folder.path()).map_err(|e| e.to_string())
+                            }
+                            None => Err("Cancelled".to_string()),
+                        }
+                    },
+                    Message::ProfilesLoaded,
+                );
+            }
+            Message::ProfilesLoaded(result) => match result {
+                Ok(profiles) => {
+                    self.error_msg = None;
+                    self.profiles = profiles;
+                }
+                Err(e) if e != "Cancelled" => {
+                    self.error_msg = Some(format!("Loading profiles failed: {}", e));
+                }
+                _ => {}
+            },
+            Message::SelectProfile(name) => match self.core.activate_profile(&name) {
+                Ok(()) => {
+                    self.config_loaded = true;
+                    self.output_overrides.clear();
+                    self.refresh_variable_catalog();
+                    self.error_msg = None;
+                    self.active_profile = Some(name);
+                }
+                Err(e) => {
+                    self.error_msg = Some(format!("Switching profile failed: {}", e));
+                }
+            },
             Message::ConfigFileLoaded(result) => match result {
                 Ok((path, content)) => {
                     if self.core.load_config(&content).is_ok() {
+                        self.active_profile = None;
                         self.config_loaded = true;
+                        self.output_overrides.clear();
+                        self.refresh_variable_catalog();
                         self.error_msg = None;
                         log::info!("Config loaded from {:?}", path);
                     } else {
@@ -487,10 +842,44 @@ impl Application for OpenFliteApp {
             Message::SelectBoard(board) => {
                 self.selected_board = Some(board);
             }
+            Message::DetectBoard => {
+                self.flash_status = Some("Detecting board...".to_string());
+                let port = self.flash_port();
+                return Command::perform(
+                    async move {
+                        // avrdude blocks for seconds; keep it off the UI executor
+                        tokio::task::spawn_blocking(move || {
+                            let port = match port {
+                                Some(port) => port,
+                                None => openflite_core::device::MobiFlightDevice::scan()
+                                    .map_err(|e| e.to_string())?
+                                    .into_iter()
+                                    .next()
+                                    .ok_or_else(|| "No serial ports found".to_string())?,
+                            };
+                            let board = openflite_core::flash::detect_board(&port)
+                                .map_err(|e| e.to_string())?;
+                            Ok((port, board.display_name().to_string()))
+                        })
+                        .await
+                        .map_err(|e| e.to_string())?
+                    },
+                    Message::BoardDetected,
+                );
+            }
+            Message::BoardDetected(result) => match result {
+                Ok((port, board)) => {
+                    self.flash_status = Some(format!("Detected {} on {}", board, port));
+                    self.selected_board = Some(board);
+                    self.flash_port = Some(port);
+                }
+                Err(e) => {
+                    self.flash_status = Some(format!("Detection failed: {}", e));
+                }
+            },
             Message::FlashFirmware => {
-                if let (Some(board_name), Some(port)) = (&self.selected_board, self.devices.first())
-                {
-                    let port = port.clone();
+                let port = self.flash_port();
+                if let (Some(board_name), Some(port)) = (&self.selected_board, port) {
                     let board = match board_name.as_str() {
                         "Arduino Mega" => openflite_core::flash::BoardType::ArduinoMega,
                         "Arduino Pro Micro" => openflite_core::flash::BoardType::ArduinoProMicro,
@@ -503,7 +892,11 @@ impl Application for OpenFliteApp {
                     return Command::perform(
                         async move {
                             // Check if avrdude is available
-                            if !openflite_core::flash::check_avrdude() {
+                            let found =
+                                tokio::task::spawn_blocking(openflite_core::flash::check_avrdude)
+                                    .await
+                                    .unwrap_or(false);
+                            if !found {
                                 return Err(
                                     "avrdude not found. Please install avrdude.".to_string()
                                 );
@@ -559,8 +952,8 @@ impl Application for OpenFliteApp {
     }
 
     fn view(&self) -> Element<'_, Message> {
-        let is_sim_connected = self.sim_status == "Connected";
-        let is_demo_mode = self.sim_status == "Demo Mode";
+        let is_sim_connected = self.sim_status == SimStatus::Connected;
+        let is_demo_mode = self.sim_status == SimStatus::Demo;
 
         column![
             self.view_header(),
@@ -582,6 +975,24 @@ impl OpenFliteApp {
             row![
                 text("OPENFLITE").size(28).style(styles::ACCENT_CYAN),
                 horizontal_space().width(Length::Fill),
+                pick_list(
+                    self.profiles.clone(),
+                    self.active_profile.clone(),
+                    Message::SelectProfile
+                )
+                .placeholder("Profile")
+                .text_size(12),
+                horizontal_space().width(10),
+                button(text("PROFILES…").size(12))
+                    .on_press(Message::LoadProfileDir)
+                    .padding([8, 16])
+                    .style(iced::theme::Button::Secondary),
+                horizontal_space().width(15),
+                button(text("⏻ ALL OUTPUTS OFF").size(12))
+                    .on_press(Message::AllOutputsOff)
+                    .padding([8, 16])
+                    .style(iced::theme::Button::Destructive),
+                horizontal_space().width(15),
                 button(
                     text(if self.show_editor {
                         "✕ CLOSE"
@@ -728,14 +1139,17 @@ impl OpenFliteApp {
                         self.devices
                             .iter()
                             .map(|dev| {
+                                let status = if dev.connected {
+                                    Color::from_rgb(0.0, 1.0, 0.5)
+                                } else {
+                                    Color::from_rgb(1.0, 0.3, 0.3)
+                                };
                                 row![
                                     container(horizontal_space().width(8))
                                         .width(8)
                                         .height(8)
-                                        .style(|_t: &Theme| container::Appearance {
-                                            background: Some(iced::Background::Color(
-                                                Color::from_rgb(0.0, 1.0, 0.5)
-                                            )),
+                                        .style(move |_t: &Theme| container::Appearance {
+                                            background: Some(iced::Background::Color(status)),
                                             border: iced::Border {
                                                 radius: 4.0.into(),
                                                 ..Default::default()
@@ -744,11 +1158,39 @@ impl OpenFliteApp {
                                         }),
                                     horizontal_space().width(10),
                                     text(dev).size(16),
+                                    horizontal_space().width(10),
+                                    text(format!("{} · v{}", dev.serial, dev.version))
+                                        .size(12)
+                                        .style(styles::TEXT_SECONDARY),
+                                    horizontal_space().width(Length::Fill),
+                                    match self.device_status.get(&dev.serial) {
+                                        Some(status) => Element::from(
+                                            text(status_summary(status)).size(12).style(
+                                                if status.is_low_on_ram() {
+                                                    Color::from_rgb(1.0, 0.6, 0.2)
+                                                } else {
+                                                    styles::TEXT_SECONDARY
+                                                },
+                                            ),
+                                        ),
+                                        None => horizontal_space().width(0).into(),
+                                    },
+                                    horizontal_space().width(10),
+                                    button(text("STATUS").size(11))
+                                        .on_press(Message::ReadDeviceStatus(dev.serial.clone()))
+                                        .padding([4, 10])
+                                        .style(iced::theme::Button::Secondary),
                                 ]
                                 .align_items(Alignment::Center)
                                 .padding(5)
                                 .into()
                             })
+                            .chain(self.skipped_ports.iter().map(|(port, reason)| {
+                                text(format!("{} — skipped: {}", port, reason))
+                                    .size(12)
+                                    .style(Color::from_rgb(0.5, 0.5, 0.5))
+                                    .into()
+                            }))
                             .collect::<Vec<_>>()
                     )
                     .spacing(5)
@@ -771,6 +1213,11 @@ impl OpenFliteApp {
                     )
                     .placeholder("Select Board"),
                     horizontal_space().width(10),
+                    button(text("DETECT").size(12))
+                        .on_press(Message::DetectBoard)
+                        .padding(8)
+                        .style(iced::theme::Button::Secondary),
+                    horizontal_space().width(10),
                     button(text("FLASH").size(12))
                         .on_press(Message::FlashFirmware)
                         .padding(8)
@@ -801,14 +1248,36 @@ impl OpenFliteApp {
                 vertical_space().height(20),
                 row![
                     text("STATUS: ").size(16),
-                    text(&self.sim_status).size(16).style(if is_sim_connected {
-                        Color::from_rgb(0.0, 1.0, 0.0)
-                    } else if self.sim_status == "Connecting..." {
+                    text(if self.sim_connecting {
+                        "Connecting..."
+                    } else {
+                        self.sim_status.label()
+                    })
+                    .size(16)
+                    .style(if self.sim_connecting {
                         Color::from_rgb(1.0, 0.8, 0.0)
+                    } else if is_sim_connected {
+                        Color::from_rgb(0.0, 1.0, 0.0)
                     } else {
                         Color::from_rgb(1.0, 0.3, 0.3)
                     }),
                 ],
+                if let Some(err) = &self.sim_error {
+                    Element::from(text(err).size(12).style(Color::from_rgb(1.0, 0.6, 0.2)))
+                } else {
+                    vertical_space().height(0).into()
+                },
+                if self.unknown_variables.is_empty() {
+                    Element::from(vertical_space().height(0))
+                } else {
+                    text(format!(
+                        "Not provided by the sim: {}",
+                        self.unknown_variables.join(", ")
+                    ))
+                    .size(12)
+                    .style(Color::from_rgb(1.0, 0.6, 0.2))
+                    .into()
+                },
                 vertical_space().height(20),
                 if is_sim_connected {
                     button(text("DISCONNECT FROM X-PLANE").size(14))
@@ -825,6 +1294,15 @@ impl OpenFliteApp {
                         .padding(10)
                         .style(iced::theme::Button::Secondary)
                 },
+                if is_any_connected {
+                    Element::from(vertical_space().height(0))
+                } else {
+                    text_input("DataRefs.txt path (optional)", &self.datarefs_path)
+                        .on_input(Message::DatarefsPathChanged)
+                        .size(12)
+                        .padding(6)
+                        .into()
+                },
                 vertical_space().height(10),
                 if is_demo_mode {
                     button(text("STOP DEMO MODE").size(14))
@@ -920,11 +1398,54 @@ impl OpenFliteApp {
         .into()
     }
 
+    fn fire_test_output(
+        &self,
+        m: &OutputMappingDraft,
+        value: f64,
+    ) -> Result<(), openflite_core::CoreError> {
+        let display = Display {
+            display_type: m.display_type.clone(),
+            serial: m.device.clone(),
+            pin: m.pin.clone(),
+            ..Default::default()
+        };
+        self.core.fire_output(&display, value)
+    }
+
+    fn flash_port(&self) -> Option<String> {
+        self.flash_port
+            .clone()
+            .or_else(|| self.devices.first().and_then(|d| d.port.clone()))
+    }
+
+    fn refresh_variable_catalog(&mut self) {
+        self.known_variables = self
+            .core
+            .available_variables()
+            .into_iter()
+            .map(|v| v.name)
+            .collect();
+        self.unknown_variables = self.core.unknown_variables();
+    }
+
+    fn variable_suggestions(&self, typed: &str, limit: usize) -> Vec<&str> {
+        if typed.is_empty() || self.known_variables.iter().any(|v| v == typed) {
+            return Vec::new();
+        }
+        let typed = typed.to_lowercase();
+        self.known_variables
+            .iter()
+            .filter(|v| v.to_lowercase().contains(&typed))
+            .take(limit)
+            .map(String::as_str)
+            .collect()
+    }
+
     fn generate_config_xml(&self) -> String {
         let mut outputs_xml = String::new();
         for (i, m) in self.output_mappings.iter().enumerate() {
             outputs_xml.push_str(&format!(
-                r#"<Config guid="user-{}" active="true">
+                r#"<Config guid="{}" active="true">
                     <Description>{}</Description>
                     <Settings>
                         <Source type="SimConnect" name="{}" />
@@ -932,7 +1453,7 @@ impl OpenFliteApp {
                         <Display type="{}" serial="{}" trigger="OnChange" pin="{}" />
                     </Settings>
                 </Config>"#,
-                i, m.dataref, m.dataref, m.comparison_value, m.comparison_op, m.if_value, m.else_value,
+                output_guid(i), m.dataref, m.dataref, m.comparison_value, m.comparison_op, m.if_value, m.else_value,
                 m.display_type, m.device, m.pin
             ));
         }
@@ -943,44 +1464,19 @@ impl OpenFliteApp {
                 <Inputs>{}</Inputs>
             </MobiFlightProject>"#,
             outputs_xml,
-            self.generate_inputs_xml()
+            inputs_xml(&self.input_mappings)
         )
     }
 
-    fn generate_inputs_xml(&self) -> String {
-        let mut inputs_xml = String::new();
-        for (i, m) in self.input_mappings.iter().enumerate() {
-            let action_xml = if m.input_type == "Encoder" {
-                format!(
-                    r#"<Encoder>
-                        <OnLeft type="XplaneAction" cmd="{}" />
-                        <OnRight type="XplaneAction" cmd="{}" />
-                    </Encoder>"#,
-                    m.on_left_cmd, m.on_right_cmd
-                )
-            } else {
-                format!(
-                    r#"<Button>
-                        <OnPress type="XplaneAction" cmd="{}" />
-                    </Button>"#,
-                    m.on_press_cmd
-                )
-            };
-            inputs_xml.push_str(&format!(
-                r#"<Config guid="input-{}" active="true">
-                    <Description>{}</Description>
-                    <Settings>{}</Settings>
-                </Config>"#,
-                i, m.name, action_xml
-            ));
-        }
-        inputs_xml
-    }
-
     fn view_editor_panel(&self) -> Element<'_, Message> {
         let comparison_ops: Vec<String> =
             vec![">".into(), "<".into(), "=".into(), ">=".into(), "<=".into()];
-        let display_types: Vec<String> = vec!["Pin".into(), "7Segment".into(), "LCD".into()];
+        let display_types: Vec<String> = vec![
+            "Pin".into(),
+            "Warning".into(),
+            "7Segment".into(),
+            "LCD".into(),
+        ];
 
         container(
             column![
@@ -1008,6 +1504,18 @@ impl OpenFliteApp {
                         .width(Length::Fill),
                 ]
                 .align_items(Alignment::Center),
+                row(self
+                    .variable_suggestions(&self.editor.dataref, 5)
+                    .into_iter()
+                    .map(|name| {
+                        button(text(name).size(11))
+                            .on_press(Message::EditorDatarefChanged(name.to_string()))
+                            .padding([2, 6])
+                            .style(iced::theme::Button::Text)
+                            .into()
+                    })
+                    .collect::<Vec<_>>())
+                .spacing(4),
                 vertical_space().height(10),
                 row![
                     text("If value").size(12),
@@ -1045,7 +1553,10 @@ impl OpenFliteApp {
                     horizontal_space().width(5),
                     pick_list(
                         self.devices.clone(),
-                        self.editor.target_device.clone(),
+                        self.devices
+                            .iter()
+                            .find(|d| self.editor.target_device.as_ref() == Some(&d.serial))
+                            .cloned(),
                         Message::EditorDeviceSelected
                     )
                     .placeholder("Select device"),
@@ -1079,6 +1590,60 @@ impl OpenFliteApp {
                         .padding(8)
                         .style(iced::theme::Button::Positive),
                 ],
+                vertical_space().height(10),
+                column(
+                    self.output_mappings
+                        .iter()
+                        .enumerate()
+                        .map(|(i, m)| {
+                            row![
+                                text(format!(
+                                    "{} → {} {} {}",
+                                    m.dataref, m.device, m.display_type, m.pin
+                                ))
+                                .size(12)
+                                .style(styles::TEXT_SECONDARY),
+                                horizontal_space().width(Length::Fill),
+                                slider(
+                                    0.0..=100.0,
+                                    self.output_overrides.get(&i).copied().unwrap_or(0.0),
+                                    move |v| Message::OverrideOutput(i, Some(v))
+                                )
+                                .width(100),
+                                horizontal_space().width(5),
+                                if let Some(value) = self.output_overrides.get(&i) {
+                                    Element::from(
+                                        button(text(format!("{:.0} ✕", value)).size(11))
+                                            .on_press(Message::OverrideOutput(i, None))
+                                            .padding([4, 6])
+                                            .style(iced::theme::Button::Text),
+                                    )
+                                } else {
+                                    horizontal_space().width(0).into()
+                                },
+                                horizontal_space().width(5),
+                                if m.display_type == "Warning" {
+                                    Element::from(
+                                        button(text("ACK").size(11))
+                                            .on_press(Message::AcknowledgeWarning(i))
+                                            .padding([4, 10])
+                                            .style(iced::theme::Button::Secondary),
+                                    )
+                                } else {
+                                    horizontal_space().width(0).into()
+                                },
+                                horizontal_space().width(5),
+                                button(text("TEST").size(11))
+                                    .on_press(Message::TestOutputMapping(i))
+                                    .padding([4, 10])
+                                    .style(iced::theme::Button::Secondary),
+                            ]
+                            .align_items(Alignment::Center)
+                            .into()
+                        })
+                        .collect::<Vec<_>>()
+                )
+                .spacing(4),
                 vertical_space().height(20),
                 // Input Mapping Section
                 text("Input Mapping")
@@ -1105,7 +1670,7 @@ impl OpenFliteApp {
                 .align_items(Alignment::Center),
                 vertical_space().height(10),
                 if self.editor.input_type.as_deref() == Some("Encoder") {
-                    Element::from(
+                    Element::from(column![
                         row![
                             text("Left:").size(12),
                             horizontal_space().width(5),
@@ -1122,9 +1687,41 @@ impl OpenFliteApp {
                                 .width(200),
                         ]
                         .align_items(Alignment::Center),
-                    )
+                        vertical_space().height(5),
+                        row![
+                            text("Fast left:").size(12),
+                            horizontal_space().width(5),
+                            text_input("(same as Left)", &self.editor.on_left_fast_cmd)
+                                .on_input(Message::EditorOnLeftFastCmdChanged)
+                                .padding(5)
+                                .width(200),
+                            horizontal_space().width(10),
+                            text("Fast right:").size(12),
+                            horizontal_space().width(5),
+                            text_input("(same as Right)", &self.editor.on_right_fast_cmd)
+                                .on_input(Message::EditorOnRightFastCmdChanged)
+                                .padding(5)
+                                .width(200),
+                        ]
+                        .align_items(Alignment::Center),
+                        vertical_space().height(5),
+                        row![
+                            text("Steps/detent:").size(12),
+                            horizontal_space().width(5),
+                            text_input("1", &self.editor.steps_per_detent)
+                                .on_input(Message::EditorStepsPerDetentChanged)
+                                .padding(5)
+                                .width(60),
+                            horizontal_space().width(10),
+                            checkbox("Reverse", self.editor.reverse)
+                                .on_toggle(Message::EditorReverseToggled)
+                                .size(14)
+                                .text_size(12),
+                        ]
+                        .align_items(Alignment::Center),
+                    ])
                 } else {
-                    Element::from(
+                    Element::from(column![
                         row![
                             text("OnPress:").size(12),
                             horizontal_space().width(5),
@@ -1134,7 +1731,24 @@ impl OpenFliteApp {
                                 .width(Length::Fill),
                         ]
                         .align_items(Alignment::Center),
-                    )
+                        vertical_space().height(5),
+                        row![
+                            text("OnRelease:").size(12),
+                            horizontal_space().width(5),
+                            text_input("(none)", &self.editor.on_release_cmd)
+                                .on_input(Message::EditorOnReleaseCmdChanged)
+                                .padding(5)
+                                .width(Length::Fill),
+                            horizontal_space().width(10),
+                            text("Repeat (ms):").size(12),
+                            horizontal_space().width(5),
+                            text_input("off", &self.editor.repeat_ms)
+                                .on_input(Message::EditorRepeatChanged)
+                                .padding(5)
+                                .width(60),
+                        ]
+                        .align_items(Alignment::Center),
+                    ])
                 },
                 vertical_space().height(10),
                 button(text("ADD INPUT").size(12))
@@ -1169,3 +1783,99 @@ impl OpenFliteApp {
         .into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openflite_core::config::MobiFlightProject;
+
+    fn draft(input_type: &str) -> InputMappingDraft {
+        InputMappingDraft {
+            name: "Encoder1".into(),
+            input_type: input_type.into(),
+            on_press_cmd: String::new(),
+            on_release_cmd: String::new(),
+            repeat_ms: String::new(),
+            on_left_cmd: "sim/radios/com1_fine_down".into(),
+            on_right_cmd: "sim/radios/com1_fine_up".into(),
+            on_left_fast_cmd: String::new(),
+            on_right_fast_cmd: String::new(),
+            steps_per_detent: String::new(),
+            reverse: false,
+        }
+    }
+
+    fn project(inputs: &str) -> MobiFlightProject {
+        MobiFlightProject::load(&format!(
+            "<MobiFlightProject><Outputs></Outputs><Inputs>{}</Inputs></MobiFlightProject>",
+            inputs
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_encoder_xml_includes_fast_actions() {
+        let mut encoder = draft("Encoder");
+        encoder.on_left_fast_cmd = "sim/radios/com1_coarse_down".into();
+        encoder.on_right_fast_cmd = "sim/radios/com1_coarse_up".into();
+        encoder.steps_per_detent = "4".into();
+        encoder.reverse = true;
+
+        let xml = inputs_xml(&[encoder]);
+        assert!(
+            xml.contains(r#"<OnLeftFast type="XplaneAction" cmd="sim/radios/com1_coarse_down" />"#)
+        );
+        assert!(
+            xml.contains(r#"<OnRightFast type="XplaneAction" cmd="sim/radios/com1_coarse_up" />"#)
+        );
+        assert!(xml.contains(r#"stepsPerDetent="4""#));
+        assert!(xml.contains(r#"reverse="true""#));
+
+        let project = project(&xml);
+        let encoder = project.inputs.config[0].settings.encoder.as_ref().unwrap();
+        assert_eq!(encoder.steps_per_detent, Some(4));
+        assert!(encoder.reverse);
+        assert_eq!(
+            encoder.on_right_fast.as_ref().unwrap().command.as_deref(),
+            Some("sim/radios/com1_coarse_up")
+        );
+    }
+
+    #[test]
+    fn test_status_summary() {
+        let status = DeviceStatus {
+            free_ram: 1834,
+            config_crc: 0x5A3C,
+            uptime: std::time::Duration::from_secs(93_900),
+        };
+        assert_eq!(
+            status_summary(&status),
+            "RAM 1834 B · CRC 5A3C · up 1d 02:05"
+        );
+
+        let status = DeviceStatus {
+            free_ram: 120,
+            config_crc: 0x1,
+            uptime: std::time::Duration::from_secs(61),
+        };
+        assert_eq!(
+            status_summary(&status),
+            "RAM 120 B (low) · CRC 0001 · up 00:01"
+        );
+    }
+
+    #[test]
+    fn test_blank_optional_fields_are_omitted() {
+        let mut button = draft("Button");
+        button.on_press_cmd = "sim/gear/toggle".into();
+
+        let xml = inputs_xml(&[draft("Encoder"), button]);
+        assert!(!xml.contains("Fast"));
+        assert!(!xml.contains("stepsPerDetent"));
+        assert!(!xml.contains("repeat"));
+        assert!(!xml.contains("OnRelease"));
+
+        let project = project(&xml);
+        assert!(project.inputs.config[1].settings.button.is_some());
+    }
+}
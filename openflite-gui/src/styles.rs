@@ -1,3 +1,5 @@
+#![allow(dead_code)]
+
 use iced::widget::container;
 use iced::{Color, Theme};
 